@@ -0,0 +1,8 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use td_print_converter::printer::{find_model, invariants};
+
+fuzz_target!(|data: &[u8]| {
+    invariants::check_decode(find_model("cz-6pv1").unwrap(), data);
+});
@@ -3,38 +3,57 @@ use gtk::prelude::*;
 use gtk::{gdk, gdk_pixbuf, glib, Orientation};
 use glib::{clone};
 use std::sync::{Arc, Mutex, mpsc::channel};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::io::Read;
 use std::time::Duration;
 use std::thread;
-use std::ops::Deref;
+use std::path::{Path, PathBuf};
 use serialport;
 use image::RgbImage;
-use image::imageops;
 
-use crate::ipp_print;
-use crate::printer::Printer;
-use crate::printer::*;
+#[cfg(feature = "ipp")]
+use td_print_converter::ipp;
+use td_print_converter::output;
+use td_print_converter::printer::Printer;
+use td_print_converter::printer::*;
 
-pub(crate) fn gui_main() {
+pub(crate) fn gui_main(options: PrinterOptions, inks: Inks, dot_scale: u32) {
     let application = gtk::Application::new(
         Some("com.thomasdaede.td-printer-converter"),
         Default::default(),
     );
-    application.connect_activate(build_ui);
+    application.connect_activate(move |application| build_ui(application, options.clone(), inks.clone(), dot_scale));
     let cli_args = Vec::<String>::new();
     application.run_with_args(&cli_args);
 }
 
 struct PrinterConfig {
-    printer: String
+    model: &'static Model
 }
 
 #[derive(Default)]
 struct PageInfo {
     covered_x: u32,
-    covered_y: u32
+    covered_y: u32,
+    dpi: (u32, u32),
+    pages: Vec<Page>
 }
 
-fn build_ui(application: &gtk::Application) {
+/// Cropped images and resolutions of the finished pages and the one being printed.
+fn cropped_pages(img: &Canvas, page_info: &PageInfo, inks: &Inks, dot_scale: u32) -> Vec<(RgbImage, (u32, u32))> {
+    let current = Page { canvas: img.clone(), covered: (page_info.covered_x, page_info.covered_y), dpi: page_info.dpi };
+    page_info.pages.iter().chain((!current.is_blank()).then_some(&current))
+        .filter_map(|page| match inks.composite(&output::crop(page), dot_scale) {
+            Ok(img) => Some((img, (page.dpi.0 * dot_scale, page.dpi.1 * dot_scale))),
+            Err(e) => {
+                eprintln!("Failed to render a page: {e}");
+                None
+            },
+        })
+        .collect()
+}
+
+fn build_ui(application: &gtk::Application, options: PrinterOptions, inks: Inks, dot_scale: u32) {
     let window = gtk::ApplicationWindow::new(application);
     window.set_title(Some("td-printer-converter"));
     window.set_default_size(500, 500);
@@ -51,24 +70,22 @@ fn build_ui(application: &gtk::Application) {
     let clear_button = gtk::Button::new();
     clear_button.set_label("Clear");
 
-    let supported_printers = [
-        "cz-8pc4",
-        "cz-6pv1",
-        "pc-pr101",
-    ];
-
-    let drop_down = gtk::DropDown::from_strings(&supported_printers);
+    let model_names: Vec<&str> = MODELS.iter().map(|model| model.name).collect();
+    let drop_down = gtk::DropDown::from_strings(&model_names);
 
     let save_button = gtk::Button::new();
     save_button.set_label("Save");
 
+    #[cfg(feature = "ipp")]
     let print_button = gtk::Button::new();
+    #[cfg(feature = "ipp")]
     print_button.set_label("Print");
 
     let button_box = gtk::Box::new(Orientation::Vertical, 0);
     button_box.append(&clear_button);
     button_box.append(&drop_down);
     button_box.append(&save_button);
+    #[cfg(feature = "ipp")]
     button_box.append(&print_button);
 
     let top_box = gtk::Box::new(Orientation::Horizontal, 0);
@@ -80,56 +97,39 @@ fn build_ui(application: &gtk::Application) {
 
     let (tx_config, rx_config) = channel();
 
-    let img = RgbImage::from_pixel(Cz8pc4::PAGE_WIDTH, Cz8pc4::PAGE_HEIGHT, image::Rgb([255,255,255]));
-    let img_arc_mutex = Arc::new(Mutex::new(img));
+    let img_arc_mutex = Arc::new(Mutex::new((MODELS[0].new)(&options).create_image()));
     let page_info_arc_mutex = Arc::new(Mutex::new(PageInfo::default()));
 
     let img_arc_mutex_redraw = Arc::clone(&img_arc_mutex);
+    // bumped by the decoder thread whenever it may have changed the image
+    let generation = Arc::new(AtomicU64::new(0));
 
     let update_printer = clone!(#[strong] drop_down, move || {
         let config = PrinterConfig {
-            printer: supported_printers[drop_down.selected() as usize].to_string()
+            model: &MODELS[drop_down.selected() as usize]
         };
         tx_config.send(config).unwrap();
     });
 
-    save_button.connect_clicked(clone!(#[strong] img_arc_mutex, #[strong] page_info_arc_mutex,  move |_| {
+    save_button.connect_clicked(clone!(#[strong] img_arc_mutex, #[strong] page_info_arc_mutex, #[strong] inks, move |_| {
         let img = img_arc_mutex.lock().unwrap();
         let page_info = page_info_arc_mutex.lock().unwrap();
-        let mut start_y: u32 = u32::MAX;
-        for (y, row) in img.rows().enumerate() {
-            for pixel in row {
-                if pixel[0] != 255 && pixel[1] != 255 && pixel[2] != 255 {
-                    start_y = y as u32;
-                    break;
-                }
-            }
-            if start_y != u32::MAX {
-                break;
+        let pages = cropped_pages(&img, &page_info, &inks, dot_scale);
+        for (page, (img_cropped, dpi)) in pages.iter().enumerate() {
+            let path = if pages.len() > 1 { output::numbered(Path::new("print.png"), page + 1) } else { PathBuf::from("print.png") };
+            if let Err(e) = output::save(img_cropped, &path, *dpi) {
+                eprintln!("Failed to save {}: {e}", path.display());
             }
         }
-        let img_cropped = imageops::crop_imm(img.deref(), 0, start_y, page_info.covered_x, page_info.covered_y - start_y).to_image();
-        img_cropped.save("print.png").unwrap();
     }));
 
-
-    print_button.connect_clicked(clone!(#[strong] img_arc_mutex, #[strong] page_info_arc_mutex, move |_| {
+    #[cfg(feature = "ipp")]
+    print_button.connect_clicked(clone!(#[strong] img_arc_mutex, #[strong] page_info_arc_mutex, #[strong] inks, move |_| {
         let img = img_arc_mutex.lock().unwrap();
         let page_info = page_info_arc_mutex.lock().unwrap();
-        let mut start_y: u32 = u32::MAX;
-        for (y, row) in img.rows().enumerate() {
-            for pixel in row {
-                if pixel[0] != 255 && pixel[1] != 255 && pixel[2] != 255 {
-                    start_y = y as u32;
-                    break;
-                }
-            }
-            if start_y != u32::MAX {
-                break;
-            }
+        for (img_cropped, _) in cropped_pages(&img, &page_info, &inks, dot_scale) {
+            ipp::print("http://CP1500fb99b1.local:631", &img_cropped);
         }
-        let img_cropped = imageops::crop_imm(img.deref(), 0, start_y, page_info.covered_x, page_info.covered_y - start_y).to_image();
-        ipp_print(&"http://CP1500fb99b1.local:631".to_string(), img_cropped);
     }));
 
     clear_button.connect_clicked(clone!(#[strong] update_printer, move |_| {
@@ -139,8 +139,23 @@ fn build_ui(application: &gtk::Application) {
         update_printer();
     });
 
-    glib::timeout_add_local(Duration::from_millis(16), clone!(#[strong] picture, move || {
-        let img = img_arc_mutex_redraw.lock().unwrap();
+    let mut drawn_generation = None;
+    glib::timeout_add_local(Duration::from_millis(16), clone!(#[strong] picture, #[strong] generation, move || {
+        // compositing the whole page is slow, so only redraw after a change
+        // and without holding up the decoder
+        let current_generation = generation.load(Ordering::Acquire);
+        if drawn_generation == Some(current_generation) {
+            return glib::ControlFlow::Continue;
+        }
+        drawn_generation = Some(current_generation);
+        let canvas = img_arc_mutex_redraw.lock().unwrap().clone();
+        let img = match inks.composite(&canvas, dot_scale) {
+            Ok(img) => img,
+            Err(e) => {
+                eprintln!("Failed to render the page: {e}");
+                return glib::ControlFlow::Continue;
+            },
+        };
         let pixbuf = gdk_pixbuf::Pixbuf::from_bytes(&glib::Bytes::from(img.as_raw()),
                                                     gdk_pixbuf::Colorspace::Rgb, false, 8,
                                                     img.width() as i32, img.height() as i32,
@@ -153,24 +168,28 @@ fn build_ui(application: &gtk::Application) {
 
     let img_arc_mutex_thread = Arc::clone(&img_arc_mutex);
     thread::spawn(move || {
-        let mut printer: Box<dyn Printer> = Box::new(Cz8pc4::default());
+        let mut printer = (MODELS[0].new)(&options);
         let mut serial_port = serialport::new("/dev/ttyACM0", 1000000).open().expect("Failed to open port");
         serial_port.set_timeout(Duration::from_secs(1)).unwrap();
+        let mut buf = [0; 4096];
         loop {
-            let (covered_x_decode, covered_y_decode) = printer.decode(&mut serial_port, &img_arc_mutex_thread);
-            let mut page_info = page_info_arc_mutex.lock().unwrap();
-            page_info.covered_x = covered_x_decode;
-            page_info.covered_y = covered_y_decode;
-            println!("Set page_info to {covered_x_decode}, {covered_y_decode}");
+            if let Ok(bytes_read) = serial_port.read(&mut buf) {
+                if let Err(e) = printer.feed(&buf[..bytes_read], &img_arc_mutex_thread) {
+                    eprintln!("Decode error: {e}");
+                }
+                let (covered_x_decode, covered_y_decode) = printer.covered();
+                let mut page_info = page_info_arc_mutex.lock().unwrap();
+                page_info.covered_x = covered_x_decode;
+                page_info.covered_y = covered_y_decode;
+                page_info.dpi = printer.dpi();
+                page_info.pages.extend(printer.take_pages());
+                generation.fetch_add(1, Ordering::Release);
+            }
             if let Ok(config) = rx_config.try_recv() {
-                printer = match config.printer.as_str() {
-                    "cz-8pc4" => Box::new(Cz8pc4::default()),
-                    "cz-6pv1" => Box::new(Cz6pv1::default()),
-                    "pc-pr101" => Box::new(Pcpr101::default()),
-                    _ => unreachable!(),
-                };
-                let mut img = img_arc_mutex.lock().unwrap();
-                img.fill(255);
+                printer = (config.model.new)(&options);
+                page_info_arc_mutex.lock().unwrap().pages.clear();
+                *img_arc_mutex.lock().unwrap() = printer.create_image();
+                generation.fetch_add(1, Ordering::Release);
             }
         }
     });
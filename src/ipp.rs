@@ -0,0 +1,30 @@
+use std::io::Cursor;
+use image::RgbImage;
+use ipp::prelude::*;
+
+/// Sends an image to an IPP printer as a JPEG, scaled to fit the page
+/// without margins.
+pub fn print(uri: &str, img: &RgbImage) {
+    let uri: Uri = uri.parse().unwrap();
+    let client = IppClient::new(uri.clone());
+    let jpeg_data = turbojpeg::compress_image(img, 100, turbojpeg::Subsamp::None).unwrap();
+    let jpeg_data_read = jpeg_data.to_vec();
+    let ipp_payload = IppPayload::new(Cursor::new(jpeg_data_read));
+    let print_operation = IppOperationBuilder::print_job(uri, ipp_payload)
+        .attribute(IppAttribute::new("document-format", IppValue::MimeMediaType("image/jpeg".to_string())))
+        .attribute(IppAttribute::new("print-scaling", IppValue::Keyword("fit".to_string())))
+        .attribute(IppAttribute::new("media-col", IppValue::Collection(vec!(
+            IppValue::MemberAttrName("media-bottom-margin".to_string()), IppValue::Integer(0),
+            IppValue::MemberAttrName("media-left-margin".to_string()), IppValue::Integer(0),
+            IppValue::MemberAttrName("media-right-margin".to_string()), IppValue::Integer(0),
+            IppValue::MemberAttrName("media-top-margin".to_string()), IppValue::Integer(0),
+        ))))
+        .build();
+    let resp = client.send(print_operation).unwrap();
+    if resp.header().status_code().is_success() {
+        eprintln!("Sent to printer!");
+    } else {
+        eprintln!("Failed to send to printer!");
+        dbg!(resp.attributes());
+    }
+}
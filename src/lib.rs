@@ -0,0 +1,13 @@
+//! Converters for print jobs of vintage Sharp and NEC printers.
+//!
+//! Each supported printer is a [`printer::Model`] in [`printer::MODELS`],
+//! whose [`printer::Printer`] decodes a job into [`printer::Page`]s of ink
+//! planes. [`output`] crops, renders and saves pages, [`pdf`] collects them
+//! into a document and, with the `ipp` feature, `ipp` sends an image on to
+//! a modern printer.
+
+#[cfg(feature = "ipp")]
+pub mod ipp;
+pub mod output;
+pub mod pdf;
+pub mod printer;
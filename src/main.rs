@@ -1,19 +1,22 @@
-use clap::Parser;
-use image::RgbImage;
-use image::imageops;
-use ipp::prelude::*;
+use clap::{CommandFactory, Parser};
+use clap::builder::{PossibleValue, PossibleValuesParser, TypedValueParser};
 use std::fs::File;
 use std::io::BufReader;
-use std::io::Cursor;
+use std::io::ErrorKind;
+use std::io::Read;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 use std::sync::Mutex;
-use turbojpeg;
 
-mod printer;
-mod gui;
+#[cfg(feature = "ipp")]
+use td_print_converter::ipp;
+use td_print_converter::{output, pdf};
+use td_print_converter::printer::Printer;
+use td_print_converter::printer::*;
 
-use crate::printer::Printer;
-use printer::*;
+#[cfg(feature = "gui")]
+mod gui;
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
@@ -27,111 +30,317 @@ struct Args {
     #[arg(long)]
     serial: Option<String>,
 
+    #[cfg(feature = "ipp")]
     #[arg(long)]
     print: Option<String>,
 
-    #[arg(long, default_value="cz-8pc4")]
-    printer: String,
+    /// Resample the output to square pixels at this resolution instead of the
+    /// printer's native one
+    #[arg(long, value_parser=clap::value_parser!(u32).range(1..))]
+    output_dpi: Option<u32>,
+
+    /// Print dot matrix jobs as one continuous banner instead of pages
+    #[arg(long)]
+    banner: bool,
+
+    /// Longest banner in inches, anything further down is dropped
+    #[arg(long, default_value_t = 50)]
+    banner_max_length: u32,
+
+    /// Write all pages of a job into one image instead of numbered files
+    /// (out-001.png, out-002.png, ...)
+    #[arg(long)]
+    join_pages: bool,
+
+    /// With a .pdf output file, collect the jobs of a whole serial session in
+    /// one PDF instead of writing one per job, numbered like out-001.pdf
+    #[arg(long)]
+    pdf_per_session: bool,
+
+    /// Also write every ink plane to its own image (out-K.png, out-Y.png, ...),
+    /// one bit per pixel for dot matrix printers unless resampled. Not
+    /// available with a .pdf output
+    #[arg(long)]
+    separations: bool,
+
+    /// Ink colours and dot shape used to composite the output
+    #[arg(long, default_value=INK_PRESETS[0].id, value_parser=ink_preset_parser())]
+    inks: &'static InkPreset,
+
+    /// Override one colour of the ink preset, e.g. M=de247a or paper=fffff0
+    #[arg(long, value_parser=parse_ink)]
+    ink: Vec<(Option<u32>, [u8; 3])>,
+
+    /// Override how far dots spread past their cell, from 0 to 1 dot
+    #[arg(long, value_parser=parse_dot_gain)]
+    dot_gain: Option<f32>,
+
+    /// Override whether dots are rendered round
+    #[arg(long)]
+    round_dots: Option<bool>,
+
+    /// Output pixels per printer dot, more than 1 shows the shape of the dots
+    #[arg(long, default_value_t = 1, value_parser=clap::value_parser!(u32).range(1..=16))]
+    dot_scale: u32,
+
+    #[arg(long, default_value=MODELS[0].id, value_parser=model_parser())]
+    printer: &'static Model,
 
+    /// List the supported printer models and exit
+    #[arg(long)]
+    list_printers: bool,
+
+    /// Print the CZ-8PC4 colour passes of every line in this plane order,
+    /// e.g. ymc or cmyk, for software that does not switch to colour mode
+    #[arg(long, value_parser=Cz8pc4::parse_pass_order)]
+    pass_order: Option<PassOrder>,
+
+    /// Report input that is unusual but can be decoded, such as lines with
+    /// missing colour passes
+    #[arg(long)]
+    strict: bool,
+
+    /// 24x24 dot JIS X 0208 BDF font for PC-PR101 kanji text, e.g. jiskan24.bdf
+    #[arg(long)]
+    kanji_font: Option<PathBuf>,
+
+    /// Tone curves for CZ-6PV1 gradation data, lines of a plane letter and
+    /// ink amounts from the darkest level up, e.g. "Y 255 160 70 0"
+    #[arg(long)]
+    calibration: Option<PathBuf>,
+
+    #[cfg(feature = "gui")]
     #[arg(long)]
     gui: bool,
+
+    /// Print an annotated trace of the commands in the input instead of rendering it
+    #[arg(long)]
+    disasm: bool,
+
+    /// Turn the input image into a print job for the printer, one pixel per
+    /// dot, written to the output file or sent to the serial port
+    #[arg(long)]
+    encode: bool,
+
+    /// How images are turned into dots when encoding: threshold, ordered or
+    /// floyd-steinberg
+    #[arg(long, default_value_t = Dither::default())]
+    dither: Dither,
 }
 
-fn ipp_print(print: &String, img_cropped: RgbImage) {
-    let uri: Uri = print.clone().parse().unwrap();
-    let client = IppClient::new(uri.clone());
-    let jpeg_data = turbojpeg::compress_image(&img_cropped, 100, turbojpeg::Subsamp::None).unwrap();
-    let jpeg_data_read = jpeg_data.to_vec();
-    let ipp_payload = IppPayload::new(Cursor::new(jpeg_data_read));
-    let print_operation = IppOperationBuilder::print_job(uri, ipp_payload)
-        .attribute(IppAttribute::new("document-format", IppValue::MimeMediaType("image/jpeg".to_string())))
-        .attribute(IppAttribute::new("print-scaling", IppValue::Keyword("fit".to_string())))
-        .attribute(IppAttribute::new("media-col", IppValue::Collection(vec!(
-            IppValue::MemberAttrName("media-bottom-margin".to_string()), IppValue::Integer(0),
-            IppValue::MemberAttrName("media-left-margin".to_string()), IppValue::Integer(0),
-            IppValue::MemberAttrName("media-right-margin".to_string()), IppValue::Integer(0),
-            IppValue::MemberAttrName("media-top-margin".to_string()), IppValue::Integer(0),
-        ))))
-        .build();
-    let resp = client.send(print_operation).unwrap();
-    if resp.header().status_code().is_success() {
-        eprintln!("Sent to printer!");
-    } else {
-        eprintln!("Failed to send to printer!");
-        dbg!(resp.attributes());
+fn model_parser() -> impl TypedValueParser<Value = &'static Model> {
+    PossibleValuesParser::new(MODELS.iter().map(|model| PossibleValue::new(model.id).help(model.name)))
+        .map(|id| find_model(&id).unwrap())
+}
+
+fn ink_preset_parser() -> impl TypedValueParser<Value = &'static InkPreset> {
+    PossibleValuesParser::new(INK_PRESETS.iter().map(|preset| PossibleValue::new(preset.id).help(preset.description)))
+        .map(|id| find_ink_preset(&id).unwrap())
+}
+
+fn list_printers() {
+    for model in MODELS {
+        println!("{:<10} {} - {}", model.id, model.name, model.description);
+        println!("{:<10} {}x{} dots at {}x{} dpi", "", model.page_size.0, model.page_size.1, model.dpi.0, model.dpi.1);
+    }
+}
+
+fn disasm(printer: &mut dyn Printer, input: &mut dyn Read) {
+    let img_mutex = Mutex::new(printer.create_image());
+    let mut print_trace = |trace: Trace| {
+        let raw = trace.raw.iter().take(8).map(|b| format!("{b:02x}")).collect::<Vec<_>>().join(" ");
+        let more = if trace.raw.len() > 8 { "..." } else { "" };
+        println!("{:08x}  {:<26} {}", trace.offset, raw + more, trace.description);
+    };
+    let mut buf = [0; 4096];
+    loop {
+        let bytes_read = match input.read(&mut buf) {
+            Ok(0) => break,
+            Ok(bytes_read) => bytes_read,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => {
+                println!("read error: {e}");
+                break;
+            },
+        };
+        // errors are already part of the trace
+        let _ = printer.feed_traced(&buf[0..bytes_read], &img_mutex, Some(&mut print_trace));
+    }
+    if let Err(e) = printer.finish() {
+        println!("{e}");
     }
 }
 
 fn main() {
     let args = Args::parse();
+    if args.separations && args.output.as_ref().is_some_and(|path| output::is_pdf(Path::new(path))) {
+        let message = "--separations writes an image per ink plane and cannot be used with a .pdf output";
+        Args::command().error(clap::error::ErrorKind::ArgumentConflict, message).exit();
+    }
 
-    let output = |img: &mut RgbImage, covered_x, covered_y| {
-        if covered_x == 0 || covered_y == 0 {
+    let mut inks = args.inks.inks.clone();
+    for &(color, rgb) in &args.ink {
+        match color {
+            Some(color) => inks.colors[color as usize] = rgb,
+            None => inks.paper = rgb,
+        }
+    }
+    inks.dot_gain = args.dot_gain.unwrap_or(inks.dot_gain);
+    inks.round_dots = args.round_dots.unwrap_or(inks.round_dots);
+    let dot_scale = args.dot_scale;
+
+    let mut session_pdf = pdf::Pdf::default();
+    let serial_session = args.serial.is_some();
+    let mut job = 0;
+    let mut output = |pages: Vec<Page>| {
+        if pages.is_empty() {
             eprintln!("Page is blank, not printing!");
             return;
         }
-        let mut start_y: u32 = u32::MAX;
-        for (y, row) in img.rows().enumerate() {
-            for pixel in row {
-                if pixel[0] != 255 && pixel[1] != 255 && pixel[2] != 255 {
-                    start_y = y as u32;
-                    break;
-                }
-            }
-            if start_y != u32::MAX {
-                break;
-            }
+        job += 1;
+        let mut canvases: Vec<(Canvas, (u32, u32))> = pages.iter().map(|page| (output::crop(page), page.dpi)).collect();
+        if args.join_pages && canvases.len() > 1 {
+            // all pages of a job come from the same printer
+            let dpi = canvases[0].1;
+            let joined = output::join(&canvases.into_iter().map(|(canvas, _)| canvas).collect::<Vec<_>>());
+            canvases = vec![(joined, dpi)];
         }
-        let img_cropped = imageops::crop(img, 0, start_y, covered_x, covered_y - start_y).to_image();
+        // the planes are only composited for output
+        let images: Result<Vec<_>, String> = canvases.iter().map(|(canvas, dpi)| {
+            let img_cropped = inks.composite(canvas, dot_scale)?;
+            let dpi = (dpi.0 * dot_scale, dpi.1 * dot_scale);
+            Ok(match args.output_dpi {
+                Some(output_dpi) => (output::resample(&img_cropped, dpi, output_dpi), (output_dpi, output_dpi)),
+                None => (img_cropped, dpi),
+            })
+        }).collect();
+        let images = match images {
+            Ok(images) => images,
+            Err(e) => {
+                eprintln!("Failed to render the job: {e}");
+                return;
+            },
+        };
+        #[cfg(feature = "ipp")]
         if let Some(ref print) = args.print {
-            ipp_print(print, img_cropped);
+            for (img_cropped, _) in images {
+                ipp::print(print, &img_cropped);
+            }
+            return;
+        }
+        let path = PathBuf::from(args.output.as_ref().expect("Output filename not provided"));
+        if output::is_pdf(&path) {
+            // the session PDF is written again after every job
+            if !args.pdf_per_session {
+                session_pdf = pdf::Pdf::default();
+            }
+            for (img_cropped, dpi) in &images {
+                session_pdf.add_page(img_cropped, *dpi);
+            }
+            let path = if serial_session && !args.pdf_per_session { output::numbered(&path, job) } else { path };
+            session_pdf.save(&path).unwrap();
         } else {
-            img_cropped.save(args.output.as_ref().expect("Output filename not provided")).unwrap();
+            for (page, (img_cropped, dpi)) in images.iter().enumerate() {
+                let path = if images.len() > 1 { output::numbered(&path, page + 1) } else { path.clone() };
+                if let Err(e) = output::save(img_cropped, &path, *dpi) {
+                    eprintln!("Failed to save {}: {e}", path.display());
+                    continue;
+                }
+                if !args.separations {
+                    continue;
+                }
+                let (canvas, canvas_dpi) = &canvases[page];
+                for &color in canvas.colors() {
+                    let plane_path = output::separation(&path, PLANE_NAMES[color as usize]);
+                    let plane = canvas.plane(color);
+                    match args.output_dpi {
+                        Some(output_dpi) => output::save_plane(&output::resample(&plane, *canvas_dpi, output_dpi), &plane_path, *dpi, false),
+                        None => output::save_plane(&plane, &plane_path, *canvas_dpi, !canvas.is_gradation()),
+                    }.unwrap();
+                }
+            }
         }
     };
 
-    let mut printer: Box<dyn Printer> = match args.printer.as_str() {
-        "cz-8pc4" => Box::new(Cz8pc4::default()),
-        "cz-6pv1" => Box::new(Cz6pv1{}),
-        "pc-pr101" => Box::new(Pcpr101::default()),
-        _ => panic!("Unknown printer"),
+    let options = PrinterOptions {
+        kanji_font: args.kanji_font.as_ref().map(|path| KanjiFont::load(path).expect("Failed to load kanji font")),
+        calibration: args.calibration.as_ref().map(|path| Calibration::load(path).expect("Failed to load calibration")),
+        banner_length: args.banner.then_some(args.banner_max_length),
+        on_detect: Some(|detection| eprintln!("Printer model: {detection}")),
+        pass_order: args.pass_order.clone(),
+        strict: args.strict,
     };
+    let mut printer = (args.printer.new)(&options);
 
+    if args.list_printers {
+        list_printers();
+        return;
+    }
+    #[cfg(feature = "gui")]
     if args.gui {
-        gui::gui_main();
+        gui::gui_main(options, inks.clone(), dot_scale);
+        return;
+    }
+    if args.disasm {
+        let input_file = File::open(args.input.unwrap()).unwrap();
+        disasm(printer.as_mut(), &mut BufReader::new(input_file));
+    } else if args.encode {
+        let Some(encode) = args.printer.encode else {
+            let message = format!("cannot encode for {}, choose a printer model with --printer", args.printer.name);
+            Args::command().error(clap::error::ErrorKind::InvalidValue, message).exit();
+        };
+        let img = image::open(args.input.unwrap()).expect("Failed to open image").to_rgb8();
+        let canvas = separate(&img, printer.create_image().is_gradation(), args.dither);
+        let data = encode(&canvas);
+        match args.serial {
+            Some(serial_port_name) => {
+                let mut serial_port = serialport::new(&serial_port_name, 1_000_000).timeout(Duration::from_secs(60)).open().expect("Failed to open port");
+                serial_port.write_all(&data).expect("Failed to send print job");
+                eprintln!("Sent print job of {} bytes to {}", data.len(), serial_port_name);
+            },
+            None => std::fs::write(args.output.expect("Output filename not provided"), data).unwrap(),
+        }
     } else if let Some(serial_port_name) = args.serial {
         // serial mode
         let mut serial_port = serialport::new(&serial_port_name, 1_000_000).timeout(Duration::from_secs(60)).open().expect("Failed to open port");
         eprintln!("Serial port opened on {}", serial_port_name);
         loop {
-            let mut print_job_bytes = Vec::new();
+            let img_mutex = Mutex::new(printer.create_image());
+            let mut pages = Vec::new();
+            let mut print_job_len = 0;
             let mut buf = [0; 128];
             while let Ok(bytes_read) = serial_port.read(&mut buf) {
-                print_job_bytes.extend_from_slice(&mut buf[0..bytes_read]);
-                eprint!("Print job in progress, read {} bytes...\r", print_job_bytes.len());
+                if let Err(e) = printer.feed(&buf[0..bytes_read], &img_mutex) {
+                    eprintln!("Decode error: {e}");
+                }
+                pages.extend(printer.take_pages());
+                print_job_len += bytes_read;
+                eprint!("Print job in progress, read {} bytes...\r", print_job_len);
             };
-            if print_job_bytes.len() == 0 {
+            if print_job_len == 0 {
                 continue;
             }
-            eprintln!("Print job of {} bytes complete            ", print_job_bytes.len());
-            let img = printer.create_image();
-            let img_mutex = Mutex::new(img);
-            let (covered_x, covered_y) = printer.decode(&mut print_job_bytes.as_slice(), &img_mutex);
-            let mut img_ = img_mutex.lock().unwrap();
-            output(&mut img_, covered_x, covered_y);
-            drop(img_);
+            eprintln!("Print job of {} bytes complete            ", print_job_len);
+            let covered = printer.finish().unwrap_or_else(|e| {
+                eprintln!("Decode error: {e}");
+                printer.covered()
+            });
+            pages.extend(printer.take_pages());
+            let page = Page { canvas: img_mutex.into_inner().unwrap(), covered, dpi: printer.dpi() };
+            if !page.is_blank() {
+                pages.push(page);
+            }
+            output(pages);
         }
     } else {
         // file mode
         let input_file = File::open(args.input.unwrap()).unwrap();
         let mut input = BufReader::new(input_file);
 
-        let img = printer.create_image();
-        let img_mutex = Mutex::new(img);
-
-        let (covered_x, covered_y) = printer.decode(&mut input, &img_mutex);
-
-        let mut img_ = img_mutex.lock().unwrap();
-        output(&mut img_, covered_x, covered_y);
+        let (pages, result) = printer.decode(&mut input);
+        if let Err(e) = result {
+            eprintln!("Decode error: {e}");
+        }
+        output(pages);
     }
 }
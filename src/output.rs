@@ -0,0 +1,200 @@
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
+use image::{GenericImageView, GrayImage, ImageBuffer, Pixel, RgbImage};
+use image::imageops::{self, FilterType};
+
+use crate::printer::{Canvas, Page};
+
+/// Crops a page to the printed area, starting at the first row with ink.
+pub fn crop(page: &Page) -> Canvas {
+    let (covered_x, covered_y) = page.covered;
+    let start_y = page.canvas.first_inked_row().unwrap_or(0);
+    page.canvas.crop(0, start_y, covered_x, covered_y.saturating_sub(start_y))
+}
+
+/// Stacks canvases on top of each other.
+pub fn join(canvases: &[Canvas]) -> Canvas {
+    let width = canvases.iter().map(Canvas::width).max().unwrap_or(0);
+    let height = canvases.iter().map(Canvas::height).sum();
+    let mut joined = match canvases.first() {
+        Some(canvas) if canvas.is_gradation() => Canvas::with_gradation(width, height),
+        _ => Canvas::new(width, height),
+    };
+    let mut y = 0;
+    for canvas in canvases {
+        joined.paste(canvas, 0, y);
+        y += canvas.height();
+    }
+    joined
+}
+
+/// Adds a suffix to a file name, before the extension.
+fn suffixed(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_stem().unwrap_or_default().to_os_string();
+    name.push(format!("-{suffix}"));
+    if let Some(ext) = path.extension() {
+        name.push(".");
+        name.push(ext);
+    }
+    path.with_file_name(name)
+}
+
+/// Adds a page number to a file name, out.png becomes out-001.png.
+pub fn numbered(path: &Path, page: usize) -> PathBuf {
+    suffixed(path, &format!("{page:03}"))
+}
+
+/// Adds a plane name to a file name, out.png becomes out-K.png.
+pub fn separation(path: &Path, plane: &str) -> PathBuf {
+    suffixed(path, plane)
+}
+
+/// Scales an image with the given resolution to square pixels at `output_dpi`.
+pub fn resample<I>(img: &I, dpi: (u32, u32), output_dpi: u32) -> ImageBuffer<I::Pixel, Vec<<I::Pixel as Pixel>::Subpixel>>
+where
+    I: GenericImageView,
+    I::Pixel: 'static,
+{
+    let scale = |size: u32, dpi: u32| ((size as u64 * output_dpi as u64 + dpi as u64 / 2) / dpi as u64).max(1) as u32;
+    imageops::resize(img, scale(img.width(), dpi.0), scale(img.height(), dpi.1), FilterType::Triangle)
+}
+
+/// Converts dots per inch to the dots per metre of a PNG pHYs chunk.
+fn dots_per_metre(dpi: u32) -> u32 {
+    ((dpi as u64 * 10000 + 127) / 254) as u32
+}
+
+fn is_png(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("png"))
+}
+
+/// Whether jobs saved to `path` go into a PDF rather than image files.
+pub fn is_pdf(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("pdf"))
+}
+
+/// Writes a PNG with a pHYs chunk so that it prints at the right size.
+fn write_png(path: &Path, (width, height): (u32, u32), color: png::ColorType, depth: png::BitDepth, data: &[u8], dpi: (u32, u32)) -> io::Result<()> {
+    let mut encoder = png::Encoder::new(BufWriter::new(File::create(path)?), width, height);
+    encoder.set_color(color);
+    encoder.set_depth(depth);
+    encoder.set_pixel_dims(Some(png::PixelDimensions {
+        xppu: dots_per_metre(dpi.0),
+        yppu: dots_per_metre(dpi.1),
+        unit: png::Unit::Meter,
+    }));
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(data)?;
+    writer.finish()?;
+    Ok(())
+}
+
+/// Saves an image, PNGs get a pHYs chunk so that they print at the right size.
+/// Other formats are left to the image crate.
+pub fn save(img: &RgbImage, path: &Path, dpi: (u32, u32)) -> io::Result<()> {
+    if !is_png(path) {
+        return img.save(path).map_err(io::Error::other);
+    }
+    write_png(path, img.dimensions(), png::ColorType::Rgb, png::BitDepth::Eight, img.as_raw(), dpi)
+}
+
+/// Saves one colour plane. With `bilevel` every pixel is either ink or
+/// paper and PNGs are written with one bit per pixel.
+pub fn save_plane(img: &GrayImage, path: &Path, dpi: (u32, u32), bilevel: bool) -> io::Result<()> {
+    if !is_png(path) {
+        return img.save(path).map_err(io::Error::other);
+    }
+    if !bilevel {
+        return write_png(path, img.dimensions(), png::ColorType::Grayscale, png::BitDepth::Eight, img.as_raw(), dpi);
+    }
+    let mut data = Vec::new();
+    for row in img.rows() {
+        // rows start on a byte boundary, 1 is white
+        let bits: Vec<bool> = row.map(|pixel| pixel.0[0] >= 0x80).collect();
+        data.extend(bits.chunks(8).map(|byte| byte.iter().enumerate().fold(0u8, |acc, (i, &bit)| acc | (bit as u8) << (7 - i))));
+    }
+    write_png(path, img.dimensions(), png::ColorType::Grayscale, png::BitDepth::One, &data, dpi)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn page_numbers_go_before_the_extension() {
+        assert_eq!(numbered(Path::new("dir/out.png"), 1), PathBuf::from("dir/out-001.png"));
+        assert_eq!(numbered(Path::new("out"), 12), PathBuf::from("out-012"));
+    }
+
+    #[test]
+    fn pdf_outputs_by_extension() {
+        assert!(is_pdf(Path::new("out.PDF")));
+        assert!(!is_pdf(Path::new("pdf")));
+    }
+
+    #[test]
+    fn separations_are_named_after_the_plane() {
+        assert_eq!(separation(&numbered(Path::new("out.png"), 2), "C"), PathBuf::from("out-002-C.png"));
+    }
+
+    #[test]
+    fn crop_starts_at_the_first_coloured_row() {
+        let mut canvas = Canvas::new(4, 8);
+        canvas.dot(1, 2, 3);
+        let page = Page { canvas, covered: (3, 6), dpi: (160, 180) };
+        let cropped = crop(&page);
+        assert_eq!((cropped.width(), cropped.height()), (3, 4));
+    }
+
+    #[test]
+    fn crop_keeps_a_single_printed_row() {
+        let mut canvas = Canvas::new(4, 8);
+        canvas.dot(2, 0, 0);
+        let page = Page { canvas, covered: (3, 1), dpi: (160, 180) };
+        let cropped = crop(&page);
+        assert_eq!((cropped.width(), cropped.height()), (3, 1));
+    }
+
+    #[test]
+    fn join_keeps_the_planes() {
+        let mut canvas = Canvas::new(2, 2);
+        canvas.dot(1, 1, 2);
+        let joined = join(&[Canvas::new(3, 1), canvas]);
+        assert_eq!((joined.width(), joined.height()), (3, 3));
+        assert_eq!(joined.ink(2, 1, 2), 255);
+    }
+
+    #[test]
+    fn resample_to_square_pixels() {
+        let img = RgbImage::new(320, 360);
+        assert_eq!(resample(&img, (320, 360), 300).dimensions(), (300, 300));
+        let img = RgbImage::new(1, 1);
+        assert_eq!(resample(&img, (320, 360), 72).dimensions(), (1, 1));
+    }
+
+    #[test]
+    fn png_has_physical_size() {
+        let path = std::env::temp_dir().join(format!("td-print-converter-{}.png", std::process::id()));
+        save(&RgbImage::new(2, 1), &path, (320, 360)).unwrap();
+        let decoder = png::Decoder::new(io::BufReader::new(File::open(&path).unwrap()));
+        let reader = decoder.read_info().unwrap();
+        let dims = reader.info().pixel_dims.unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!((dims.xppu, dims.yppu, dims.unit), (12598, 14173, png::Unit::Meter));
+    }
+
+    #[test]
+    fn bilevel_planes_have_one_bit_per_pixel() {
+        let path = std::env::temp_dir().join(format!("td-print-converter-plane-{}.png", std::process::id()));
+        let mut canvas = Canvas::new(10, 2);
+        canvas.dot(9, 1, 0);
+        save_plane(&canvas.plane(0), &path, (320, 360), true).unwrap();
+        let img = image::open(&path).unwrap().to_luma8();
+        let decoder = png::Decoder::new(io::BufReader::new(File::open(&path).unwrap()));
+        let depth = decoder.read_info().unwrap().info().bit_depth;
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(depth, png::BitDepth::One);
+        assert_eq!(img, canvas.plane(0));
+    }
+}
@@ -0,0 +1,108 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use image::RgbImage;
+
+/// A page waiting to be written, with its image already compressed.
+struct PdfPage {
+    width: u32,
+    height: u32,
+    /// Page size in points.
+    size: (f32, f32),
+    data: Vec<u8>,
+}
+
+/// Minimal PDF writer, every page is one image filling a page of the
+/// physical size given by its resolution.
+#[derive(Default)]
+pub struct Pdf {
+    pages: Vec<PdfPage>,
+}
+
+impl Pdf {
+    pub fn add_page(&mut self, img: &RgbImage, dpi: (u32, u32)) {
+        self.pages.push(PdfPage {
+            width: img.width(),
+            height: img.height(),
+            size: (img.width() as f32 * 72.0 / dpi.0 as f32, img.height() as f32 * 72.0 / dpi.1 as f32),
+            data: miniz_oxide::deflate::compress_to_vec_zlib(img.as_raw(), 6),
+        });
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut file = BufWriter::new(File::create(path)?);
+        self.write(&mut file)?;
+        file.flush()
+    }
+
+    /// Writes the document. Objects 1 and 2 are the catalog and the page
+    /// tree, each page then takes three objects: page, contents and image.
+    pub fn write(&self, w: &mut dyn Write) -> io::Result<()> {
+        let mut out = Vec::new();
+        let mut offsets = Vec::new();
+        out.extend_from_slice(b"%PDF-1.4\n%\xe2\xe3\xcf\xd3\n");
+
+        offsets.push(out.len());
+        out.extend_from_slice(b"1 0 obj\n<< /Type /Catalog /Pages 2 0 R >>\nendobj\n");
+        offsets.push(out.len());
+        let kids: Vec<String> = (0..self.pages.len()).map(|i| format!("{} 0 R", 3 + i * 3)).collect();
+        write!(out, "2 0 obj\n<< /Type /Pages /Kids [{}] /Count {} >>\nendobj\n", kids.join(" "), self.pages.len())?;
+
+        for (i, page) in self.pages.iter().enumerate() {
+            let id = 3 + i * 3;
+            let (width, height) = page.size;
+            offsets.push(out.len());
+            write!(out, "{id} 0 obj\n<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {width:.2} {height:.2}] \
+                /Resources << /XObject << /Im0 {} 0 R >> >> /Contents {} 0 R >>\nendobj\n", id + 2, id + 1)?;
+            let contents = format!("q {width:.2} 0 0 {height:.2} 0 0 cm /Im0 Do Q\n");
+            offsets.push(out.len());
+            write!(out, "{} 0 obj\n<< /Length {} >>\nstream\n{contents}endstream\nendobj\n", id + 1, contents.len())?;
+            offsets.push(out.len());
+            write!(out, "{} 0 obj\n<< /Type /XObject /Subtype /Image /Width {} /Height {} /ColorSpace /DeviceRGB \
+                /BitsPerComponent 8 /Filter /FlateDecode /Length {} >>\nstream\n", id + 2, page.width, page.height, page.data.len())?;
+            out.extend_from_slice(&page.data);
+            out.extend_from_slice(b"\nendstream\nendobj\n");
+        }
+
+        let xref = out.len();
+        write!(out, "xref\n0 {}\n0000000000 65535 f \n", offsets.len() + 1)?;
+        for offset in &offsets {
+            writeln!(out, "{offset:010} 00000 n ")?;
+        }
+        write!(out, "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{xref}\n%%EOF\n", offsets.len() + 1)?;
+        w.write_all(&out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pages_are_physically_sized() {
+        let mut pdf = Pdf::default();
+        pdf.add_page(&RgbImage::new(320, 180), (320, 360));
+        pdf.add_page(&RgbImage::new(160, 90), (160, 180));
+        let mut out = Vec::new();
+        pdf.write(&mut out).unwrap();
+        let text = String::from_utf8_lossy(&out);
+        assert!(text.contains("/Count 2"));
+        assert_eq!(text.matches("/MediaBox [0 0 72.00 36.00]").count(), 2);
+    }
+
+    #[test]
+    fn xref_points_at_the_objects() {
+        let mut pdf = Pdf::default();
+        pdf.add_page(&RgbImage::new(2, 2), (72, 72));
+        let mut out = Vec::new();
+        pdf.write(&mut out).unwrap();
+        let text = String::from_utf8_lossy(&out);
+        let xref: usize = text.rsplit("startxref\n").next().unwrap().lines().next().unwrap().parse().unwrap();
+        assert!(out[xref..].starts_with(b"xref\n0 6\n"));
+        let entries = String::from_utf8_lossy(&out[xref..]).lines().skip(3).take(5).map(str::to_string).collect::<Vec<_>>();
+        for (id, entry) in entries.iter().enumerate() {
+            let offset: usize = entry[..10].parse().unwrap();
+            assert!(out[offset..].starts_with(format!("{} 0 obj", id + 1).as_bytes()));
+        }
+    }
+}
@@ -0,0 +1,273 @@
+use std::fmt;
+use std::sync::Mutex;
+
+use super::{Canvas, DecodeError, Model, Page, Printer, PrinterOptions, Trace, MODELS};
+
+/// Outcome of guessing the printer model from the start of a print job.
+#[derive(Debug, Clone)]
+pub struct Detection {
+    pub model: &'static Model,
+    /// 0 to 100, 0 means nothing was recognised and `model` is a fallback.
+    pub confidence: u32,
+    /// The commands the guess is based on.
+    pub reason: String,
+}
+
+impl fmt::Display for Detection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.confidence == 0 {
+            write!(f, "could not detect the printer model ({}), falling back to {}", self.reason, self.model.id)
+        } else {
+            write!(f, "detected {} ({}) with {}% confidence: {}", self.model.id, self.model.name, self.confidence, self.reason)
+        }
+    }
+}
+
+/// Bytes looked at before deciding, unless the job ends earlier.
+const SAMPLE_LEN: usize = 512;
+
+/// A command that points at one model, with how much it counts.
+struct Evidence {
+    model: &'static str,
+    command: &'static str,
+    weight: u32,
+}
+
+fn digits(data: &[u8], start: usize, len: usize) -> Option<usize> {
+    let field = data.get(start..start + len)?;
+    field.iter().all(u8::is_ascii_digit).then(|| std::str::from_utf8(field).unwrap().parse().unwrap())
+}
+
+/// Looks for commands that only make sense for one model. Graphics data is
+/// skipped so that it is not mistaken for commands.
+fn scan(data: &[u8]) -> Vec<Evidence> {
+    let mut evidence = Vec::new();
+    let mut found = |model, command, weight| evidence.push(Evidence { model, command, weight });
+    if data.first() == Some(&0xc0) {
+        // a frame dump is all gradation data, there is nothing else to look at
+        found("cz-6pv1", "0xC0 frame", 5);
+        return evidence;
+    }
+    let mut i = 0;
+    while i < data.len() {
+        if data[i] != 0x1b || i + 1 >= data.len() {
+            i += 1;
+            continue;
+        }
+        let mut skip = 2;
+        match data[i + 1] {
+            // the PC-PR101 has ASCII decimal column counts, the CZ-8PC4 binary ones
+            b'J' | b'S' | b'I' => if let Some(columns) = digits(data, i + 2, 4) {
+                found("pc-pr101", "ESC J/S/I graphics", 3);
+                skip = 6 + columns * match data[i + 1] { b'J' => 3, b'I' => 2, _ => 1 };
+            },
+            b'M' => if let Some(count) = data.get(i + 2..i + 4) {
+                found("cz-8pc4", "ESC M graphics", 3);
+                skip = 4 + u16::from_be_bytes([count[0], count[1]]) as usize * 6;
+            },
+            b'C' if matches!(data.get(i + 2), Some(b'0' | b'3' | b'5' | b'6')) => found("pc-pr101", "ESC C colour select", 2),
+            b'F' if digits(data, i + 2, 4).is_some() => found("pc-pr101", "ESC F dot address", 2),
+            b'L' if digits(data, i + 2, 3).is_some() => found("pc-pr101", "ESC L left margin", 2),
+            b'T' if digits(data, i + 2, 2).is_some() => found("pc-pr101", "ESC T line feed pitch", 1),
+            b'K' | b'H' => found("pc-pr101", "ESC K/H kanji mode", 1),
+            0x19 => found("cz-8pc4", "ESC 0x19 colour mode", 2),
+            b'%' if digits(data, i + 2, 2).is_some() => found("cz-8pc4", "ESC % line spacing", 1),
+            _ => {},
+        }
+        i += skip;
+    }
+    evidence
+}
+
+/// Guesses the model from the start of a print job. Confidence is the share
+/// of the evidence pointing at the chosen model, lowered when there is little
+/// evidence at all.
+pub fn detect(data: &[u8]) -> Option<Detection> {
+    let evidence = scan(data);
+    let total: u32 = evidence.iter().map(|e| e.weight).sum();
+    let score = |model: &Model| evidence.iter().filter(|e| e.model == model.id).map(|e| e.weight).sum::<u32>();
+    let model = MODELS.iter().filter(|model| score(model) > 0).max_by_key(|model| score(model))?;
+    let mut commands: Vec<(&str, u32)> = Vec::new();
+    for e in evidence.iter().filter(|e| e.model == model.id) {
+        match commands.iter_mut().find(|(command, _)| *command == e.command) {
+            Some((_, count)) => *count += 1,
+            None => commands.push((e.command, 1)),
+        }
+    }
+    let reason = commands.iter().map(|(command, count)| format!("{count}x {command}")).collect::<Vec<_>>().join(", ");
+    Some(Detection {
+        model,
+        confidence: score(model) * 100 / total * total.min(6) / 6,
+        reason,
+    })
+}
+
+/// Printer that decides on the model at the start of each job. Until the
+/// first `SAMPLE_LEN` bytes are in, or the guess is confident enough, it
+/// renders with the best guess so far and starts over if the guess changes.
+/// Errors are held back until the guess is final.
+pub struct Auto {
+    options: PrinterOptions,
+    printer: Option<(&'static Model, Box<dyn Printer>)>,
+    sample: Vec<u8>,
+    error: Option<DecodeError>,
+    decided: bool,
+    finished: bool,
+}
+
+impl Auto {
+    /// Confidence needed to decide before the whole sample is in.
+    const MIN_CONFIDENCE: u32 = 50;
+
+    pub fn new(options: PrinterOptions) -> Auto {
+        Auto { options, printer: None, sample: Vec::new(), error: None, decided: false, finished: false }
+    }
+
+    fn fallback(&self) -> Detection {
+        Detection {
+            model: &MODELS[0],
+            confidence: 0,
+            reason: format!("no known commands in the first {} bytes", self.sample.len()),
+        }
+    }
+
+    fn decide(&mut self, detection: &Detection, trace: &mut Option<&mut dyn FnMut(Trace)>) {
+        self.decided = true;
+        if let Some(trace) = trace {
+            trace(Trace::new(0, &[], detection.to_string(), &Ok(())));
+        }
+        if let Some(on_detect) = self.options.on_detect {
+            on_detect(detection);
+        }
+    }
+}
+
+impl Printer for Auto {
+    /// The canvas is replaced by the one of the guessed model as soon as
+    /// there is a guess.
+    fn create_image(&self) -> Canvas {
+        // large enough for any model
+        let width = MODELS.iter().map(|model| model.page_size.0).max().unwrap();
+        let height = MODELS.iter().map(|model| model.page_size.1).max().unwrap();
+        Canvas::new(width, height)
+    }
+
+    fn dpi(&self) -> (u32, u32) {
+        self.printer.as_ref().map_or(MODELS[0].dpi, |(_, printer)| printer.dpi())
+    }
+
+    fn feed_traced(&mut self, data: &[u8], img_mutex: &Mutex<Canvas>, mut trace: Option<&mut dyn FnMut(Trace)>) -> Result<(), DecodeError> {
+        if self.finished {
+            *self = Auto::new(self.options.clone());
+        }
+        if self.decided && let Some((_, printer)) = &mut self.printer {
+            return printer.feed_traced(data, img_mutex, trace);
+        }
+        self.sample.extend_from_slice(data);
+        let detection = detect(&self.sample).unwrap_or_else(|| self.fallback());
+        if detection.confidence >= Auto::MIN_CONFIDENCE || self.sample.len() >= SAMPLE_LEN {
+            self.decide(&detection, &mut trace);
+        }
+        let result = match &mut self.printer {
+            Some((model, printer)) if std::ptr::eq(*model, detection.model) => printer.feed_traced(data, img_mutex, trace),
+            _ => {
+                self.error = None;
+                let mut printer = (detection.model.new)(&self.options);
+                *img_mutex.lock().unwrap() = printer.create_image();
+                let result = printer.feed_traced(&self.sample, img_mutex, trace);
+                self.printer = Some((detection.model, printer));
+                result
+            },
+        };
+        if let Err(e) = result {
+            self.error.get_or_insert(e);
+        }
+        if !self.decided {
+            return Ok(());
+        }
+        self.sample = Vec::new();
+        self.error.take().map_or(Ok(()), Err)
+    }
+
+    fn finish(&mut self) -> Result<(u32, u32), DecodeError> {
+        self.finished = true;
+        if !self.decided && !self.sample.is_empty() {
+            let detection = detect(&self.sample).unwrap_or_else(|| self.fallback());
+            self.decide(&detection, &mut None);
+        }
+        let finished = match &mut self.printer {
+            Some((_, printer)) => printer.finish(),
+            None => Ok((0, 0)),
+        };
+        // a held back error comes earlier in the job
+        match self.error.take() {
+            Some(e) => Err(e),
+            None => finished,
+        }
+    }
+
+    fn covered(&self) -> (u32, u32) {
+        self.printer.as_ref().map_or((0, 0), |(_, printer)| printer.covered())
+    }
+
+    /// Pages are held back until the guess is final.
+    fn take_pages(&mut self) -> Vec<Page> {
+        match &mut self.printer {
+            Some((_, printer)) if self.decided => printer.take_pages(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detected(data: &[u8]) -> Option<(&'static str, u32)> {
+        detect(data).map(|detection| (detection.model.id, detection.confidence))
+    }
+
+    #[test]
+    fn detects_each_model() {
+        assert_eq!(detected(&[0xc0, 0x80, 0x81]), Some(("cz-6pv1", 83)));
+        assert_eq!(detected(b"\x1b\x19\x1bM\x00\x01\x1bJ0048\x1b%24\r\n"), Some(("cz-8pc4", 100)));
+        assert_eq!(detected(b"\x1bC5\x1bT16\x1bJ0001\x1bM0\n"), Some(("pc-pr101", 100)));
+    }
+
+    #[test]
+    fn graphics_data_is_not_evidence() {
+        // the column data of ESC J looks like ESC 0x19 and ESC M
+        assert_eq!(detected(b"\x1bJ0002\x1b\x19\x00\x1bM\x00"), Some(("pc-pr101", 50)));
+    }
+
+    #[test]
+    fn little_evidence_lowers_confidence() {
+        assert_eq!(detected(b"HELLO\x1bT18\r\n"), Some(("pc-pr101", 16)));
+        assert_eq!(detected(b"HELLO\r\n"), None);
+    }
+
+    #[test]
+    fn renders_like_the_detected_model() {
+        let job = b"\x1bC5\x1bJ0001\x07\x00\x00\r\n";
+        let mut auto = Auto::new(PrinterOptions::default());
+        let auto_img = Mutex::new(auto.create_image());
+        // split so that the first chunk alone is not enough to decide
+        assert_eq!(auto.feed(&job[..2], &auto_img), Ok(()));
+        assert_eq!(auto.feed(&job[2..], &auto_img), Ok(()));
+        assert_eq!(auto.finish(), Ok((1, 3)));
+        let mut pcpr101 = super::super::Pcpr101::default();
+        let img = Mutex::new(pcpr101.create_image());
+        assert_eq!(pcpr101.feed(job, &img), Ok(()));
+        assert_eq!(pcpr101.finish(), Ok((1, 3)));
+        let (auto_img, img) = (auto_img.into_inner().unwrap(), img.into_inner().unwrap());
+        assert!((0..4).all(|color| auto_img.plane_data(color) == img.plane_data(color)));
+    }
+
+    #[test]
+    fn falls_back_on_plain_text() {
+        let mut auto = Auto::new(PrinterOptions::default());
+        let img = Mutex::new(auto.create_image());
+        assert_eq!(auto.feed(b"I", &img), Ok(()));
+        assert_eq!(auto.finish(), Ok((20, 35)));
+    }
+}
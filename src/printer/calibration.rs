@@ -0,0 +1,96 @@
+use std::io;
+use std::path::Path;
+
+use super::Cz6pv1;
+
+/// Tone curves turning CZ-6PV1 gradation levels into amounts of ink.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Calibration {
+    /// Ink from 0 for none to 255 at evenly spaced points from the lowest
+    /// level of a plane to the highest, for yellow, magenta and cyan. Levels
+    /// in between are interpolated.
+    curves: [Vec<u8>; 3],
+    /// Levels the second pass printing the bottom half is shifted by.
+    pub bottom_half_offset: u8,
+}
+
+impl Default for Calibration {
+    /// Linear curves, the lowest level is full ink.
+    fn default() -> Calibration {
+        Calibration {
+            curves: std::array::from_fn(|_| vec![255, 0]),
+            bottom_half_offset: Cz6pv1::BOTTOM_HALF_OFFSET,
+        }
+    }
+}
+
+impl Calibration {
+    pub fn load(path: &Path) -> io::Result<Calibration> {
+        Calibration::parse(&std::fs::read_to_string(path)?)
+    }
+
+    /// Parses a calibration file. Each line is a plane letter followed by at
+    /// least two ink amounts, darkest level first, or `offset` followed by
+    /// the bottom half offset. Planes that are not given stay linear, `#`
+    /// starts a comment.
+    ///
+    /// ```text
+    /// Y 255 190 120 60 0
+    /// offset 7
+    /// ```
+    pub fn parse(text: &str) -> io::Result<Calibration> {
+        let invalid = |line: &str| io::Error::new(io::ErrorKind::InvalidData, format!("bad calibration line {line:?}"));
+        let mut calibration = Calibration::default();
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap().trim();
+            let mut fields = line.split_whitespace();
+            let Some(name) = fields.next() else {
+                continue;
+            };
+            let values = fields.map(str::parse).collect::<Result<Vec<u8>, _>>().map_err(|_| invalid(line))?;
+            match name.to_ascii_uppercase().as_str() {
+                "OFFSET" if values.len() == 1 => calibration.bottom_half_offset = values[0],
+                plane @ ("Y" | "M" | "C") if values.len() >= 2 => {
+                    let index = ["Y", "M", "C"].iter().position(|p| *p == plane).unwrap();
+                    calibration.curves[index] = values;
+                },
+                _ => return Err(invalid(line)),
+            }
+        }
+        Ok(calibration)
+    }
+
+    /// Ink for `level` out of `levels` on the curve of a plane.
+    pub fn ink(&self, plane: usize, level: u32, levels: u32) -> u8 {
+        let curve = &self.curves[plane];
+        let position = level.min(levels - 1) as f32 * (curve.len() - 1) as f32 / (levels - 1).max(1) as f32;
+        let (i, t) = (position.floor() as usize, position.fract());
+        let next = curve[(i + 1).min(curve.len() - 1)];
+        (curve[i] as f32 + (next as f32 - curve[i] as f32) * t).round() as u8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn curves_are_interpolated() {
+        let calibration = Calibration::parse("# test\nm 255 100 0\nOFFSET 5 # shifted\n").unwrap();
+        assert_eq!(calibration.bottom_half_offset, 5);
+        assert_eq!(calibration.ink(1, 0, 5), 255);
+        assert_eq!(calibration.ink(1, 1, 5), 178);
+        assert_eq!(calibration.ink(1, 2, 5), 100);
+        assert_eq!(calibration.ink(1, 4, 5), 0);
+        // the other planes stay linear
+        assert_eq!(calibration.ink(0, 2, 5), 128);
+    }
+
+    #[test]
+    fn bad_lines_are_rejected() {
+        assert!(Calibration::parse("K 255 0").is_err());
+        assert!(Calibration::parse("Y 255").is_err());
+        assert!(Calibration::parse("C 255 256").is_err());
+        assert!(Calibration::parse("offset").is_err());
+    }
+}
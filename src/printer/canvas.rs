@@ -0,0 +1,174 @@
+use image::{GrayImage, Luma, RgbImage};
+
+use super::Inks;
+
+/// Plane names for file names, indexed by colour.
+pub const PLANE_NAMES: [&str; 4] = ["K", "Y", "M", "C"];
+
+/// The paper a job is printed on. The ink of every colour is kept in its own
+/// plane, indexed by colour (0 = black, 1 = yellow, 2 = magenta, 3 = cyan), as
+/// the amount of ink from 0 for none to 255.
+#[derive(Clone)]
+pub struct Canvas {
+    width: u32,
+    height: u32,
+    planes: [Vec<u8>; 4],
+    gradation: bool,
+}
+
+impl Canvas {
+    /// A blank canvas for dot matrix printers, where every dot is either
+    /// inked or not.
+    pub fn new(width: u32, height: u32) -> Canvas {
+        let size = width as usize * height as usize;
+        Canvas { width, height, planes: std::array::from_fn(|_| vec![0; size]), gradation: false }
+    }
+
+    /// A blank canvas for printers with gradation and no black ink, such as
+    /// the CZ-6PV1.
+    pub fn with_gradation(width: u32, height: u32) -> Canvas {
+        Canvas { gradation: true, ..Canvas::new(width, height) }
+    }
+
+    /// A blank canvas of the same size and kind.
+    pub fn blank(&self) -> Canvas {
+        Canvas { gradation: self.gradation, ..Canvas::new(self.width, self.height) }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn is_gradation(&self) -> bool {
+        self.gradation
+    }
+
+    /// The colours the printer has ink for.
+    pub fn colors(&self) -> &'static [u32] {
+        if self.gradation { &[1, 2, 3] } else { &[0, 1, 2, 3] }
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        assert!(x < self.width && y < self.height, "dot ({x}, {y}) outside of {}x{} canvas", self.width, self.height);
+        y as usize * self.width as usize + x as usize
+    }
+
+    pub fn ink(&self, color: u32, x: u32, y: u32) -> u8 {
+        self.planes[color as usize][self.index(x, y)]
+    }
+
+    /// The ink of one colour for every dot, row by row.
+    pub fn plane_data(&self, color: u32) -> &[u8] {
+        &self.planes[color as usize]
+    }
+
+    pub fn set(&mut self, color: u32, x: u32, y: u32, ink: u8) {
+        let i = self.index(x, y);
+        self.planes[color as usize][i] = ink;
+    }
+
+    /// Applies one dot of the given colour.
+    pub fn dot(&mut self, x: u32, y: u32, color: u32) {
+        self.set(color, x, y, 255);
+    }
+
+    /// Changes the number of rows, new rows are blank.
+    pub fn set_height(&mut self, height: u32) {
+        for plane in &mut self.planes {
+            plane.resize(self.width as usize * height as usize, 0);
+        }
+        self.height = height;
+    }
+
+    /// The first row with any ink on it.
+    pub fn first_inked_row(&self) -> Option<u32> {
+        let width = self.width as usize;
+        (0..self.height).find(|&y| {
+            let row = y as usize * width..(y as usize + 1) * width;
+            self.planes.iter().any(|plane| plane[row.clone()].iter().any(|&ink| ink != 0))
+        })
+    }
+
+    /// Copies a rectangle, clipped to the canvas.
+    pub fn crop(&self, x: u32, y: u32, width: u32, height: u32) -> Canvas {
+        let x = x.min(self.width);
+        let y = y.min(self.height);
+        let width = width.min(self.width - x);
+        let height = height.min(self.height - y);
+        let mut cropped = Canvas { gradation: self.gradation, ..Canvas::new(width, height) };
+        for (plane, cropped_plane) in self.planes.iter().zip(&mut cropped.planes) {
+            for row in 0..height as usize {
+                let start = (y as usize + row) * self.width as usize + x as usize;
+                cropped_plane[row * width as usize..(row + 1) * width as usize].copy_from_slice(&plane[start..start + width as usize]);
+            }
+        }
+        cropped
+    }
+
+    /// Copies another canvas in with its top left corner at `(x, y)`, clipped
+    /// to this canvas.
+    pub fn paste(&mut self, other: &Canvas, x: u32, y: u32) {
+        let width = other.width.min(self.width.saturating_sub(x)) as usize;
+        let height = other.height.min(self.height.saturating_sub(y));
+        for (plane, other_plane) in self.planes.iter_mut().zip(&other.planes) {
+            for row in 0..height as usize {
+                let start = (y as usize + row) * self.width as usize + x as usize;
+                let other_start = row * other.width as usize;
+                plane[start..start + width].copy_from_slice(&other_plane[other_start..other_start + width]);
+            }
+        }
+    }
+
+    /// One plane as a greyscale image with the ink in black.
+    pub fn plane(&self, color: u32) -> GrayImage {
+        GrayImage::from_fn(self.width, self.height, |x, y| Luma([255 - self.ink(color, x, y)]))
+    }
+
+    /// Composites the planes to RGB with the default inks, one pixel per dot.
+    pub fn composite(&self) -> Result<RgbImage, String> {
+        Inks::default().composite(self, 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgb;
+
+    #[test]
+    fn planes_are_kept_apart() {
+        let mut canvas = Canvas::new(3, 2);
+        canvas.dot(0, 0, 1);
+        canvas.dot(0, 0, 3);
+        canvas.dot(1, 1, 0);
+        canvas.dot(1, 1, 2);
+        canvas.set(2, 2, 1, 128);
+        let img = canvas.composite().unwrap();
+        assert_eq!(img.get_pixel(0, 0), &Rgb([0, 255, 0]));
+        assert_eq!(img.get_pixel(1, 1), &Rgb([0, 0, 0]));
+        assert_eq!(img.get_pixel(2, 1), &Rgb([255, 127, 255]));
+        assert_eq!(canvas.plane(1).get_pixel(0, 0), &Luma([0]));
+        assert_eq!(canvas.plane(2).get_pixel(0, 0), &Luma([255]));
+        assert_eq!(canvas.plane(2).get_pixel(1, 1), &Luma([0]));
+    }
+
+    #[test]
+    fn crop_and_grow() {
+        let mut canvas = Canvas::new(4, 4);
+        canvas.dot(2, 3, 2);
+        assert_eq!(canvas.first_inked_row(), Some(3));
+        let cropped = canvas.crop(1, 2, 10, 10);
+        assert_eq!((cropped.width(), cropped.height()), (3, 2));
+        assert_eq!(cropped.ink(2, 1, 1), 255);
+        let mut pasted = Canvas::new(4, 4);
+        pasted.paste(&cropped, 1, 2);
+        assert_eq!(pasted.ink(2, 2, 3), 255);
+        canvas.set_height(6);
+        assert_eq!(canvas.ink(2, 2, 3), 255);
+        assert_eq!(canvas.ink(2, 2, 5), 0);
+    }
+}
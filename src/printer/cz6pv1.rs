@@ -0,0 +1,325 @@
+use std::sync::Mutex;
+
+use super::{eject, feed_commands, finish_commands, Calibration, Canvas, Decode, DecodeError, Page, Parse, Parsed, Printer, Tokenizer, Trace};
+
+/// A single command in a CZ-6PV1 byte stream.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// 0xC0, full frame dump followed by 3 planes of 992 lines
+    Frame,
+    /// One line of gradation data for the current plane, shorter if the
+    /// frame was cut off in the middle of it
+    Line(Vec<u8>),
+    /// A command arrived before the frame was complete, after the given
+    /// number of lines. Takes no bytes.
+    FrameEnd(u32),
+}
+
+#[derive(Default)]
+pub struct Parser {
+    lines_left: u32,
+    start: usize,
+}
+
+impl Parse for Parser {
+    type Command = Command;
+
+    fn parse(&mut self, buf: &[u8], offset: usize) -> Parsed<Command> {
+        if self.lines_left > 0 {
+            // gradation data never goes above 0xBF, anything higher is the
+            // next command and ends the frame early
+            let available = &buf[..buf.len().min(Cz6pv1::PAGE_WIDTH as usize)];
+            return match available.iter().position(|&b| b >= Cz6pv1::FIRST_COMMAND) {
+                Some(0) => {
+                    let lines = Cz6pv1::FRAME_LINES - self.lines_left;
+                    self.lines_left = 0;
+                    Parsed::Command(0, Command::FrameEnd(lines))
+                },
+                Some(len) => {
+                    self.lines_left -= 1;
+                    Parsed::Command(len, Command::Line(available[..len].to_vec()))
+                },
+                None if available.len() == Cz6pv1::PAGE_WIDTH as usize => {
+                    self.lines_left -= 1;
+                    Parsed::Command(available.len(), Command::Line(available.to_vec()))
+                },
+                None => Parsed::Incomplete,
+            };
+        }
+        let Some(&c) = buf.first() else {
+            return Parsed::Incomplete;
+        };
+        match c {
+            0xC0 => {
+                self.lines_left = Cz6pv1::FRAME_LINES;
+                self.start = offset;
+                Parsed::Command(1, Command::Frame)
+            },
+            _ => Parsed::Invalid(1, DecodeError::UnknownCommand { offset, code: c }),
+        }
+    }
+
+    fn in_progress(&self) -> Option<usize> {
+        (self.lines_left > 0).then_some(self.start)
+    }
+}
+
+pub struct Cz6pv1 {
+    tokens: Tokenizer<Parser>,
+    line: u32, // line within the frame, counting all planes
+    covered_y: u32,
+    tables: [[[u8; 256]; 2]; 3], // ink for each value, by plane and half
+    pages: Vec<Page>,
+}
+
+impl Default for Cz6pv1 {
+    fn default() -> Cz6pv1 {
+        Cz6pv1 {
+            tokens: Tokenizer::default(),
+            line: 0,
+            covered_y: 0,
+            tables: Cz6pv1::tables(&Calibration::default()),
+            pages: Vec::new(),
+        }
+    }
+}
+
+impl Cz6pv1 {
+    pub const PAGE_WIDTH: u32 = 0x200;
+    pub const PAGE_HEIGHT: u32 = 992;
+    /// Nominal, the print is roughly 3.2 inches wide with square pixels.
+    pub const DPI: (u32, u32) = (160, 160);
+    const Y_MIN: u8 = 0x80;
+    const Y_MAX: u8 = 0xBF;
+    const M_MIN: u8 = 0x40;
+    const M_MAX: u8 = 0x7E;
+    const C_MIN: u8 = 0x00;
+    const C_MAX: u8 = 0x3E;
+    /// Values of the planes in the order they are sent, higher values leave
+    /// more light.
+    const RANGES: [(u8, u8); 3] = [(Cz6pv1::Y_MIN, Cz6pv1::Y_MAX), (Cz6pv1::M_MIN, Cz6pv1::M_MAX), (Cz6pv1::C_MIN, Cz6pv1::C_MAX)];
+    /// The rows from here on are printed in a second pass.
+    const SPLIT_ROW: u32 = 512;
+    /// The levels of the second pass start this much higher, anything below
+    /// is full ink.
+    pub const BOTTOM_HALF_OFFSET: u8 = 7;
+    /// Lines of a whole frame, all three planes.
+    const FRAME_LINES: u32 = 3 * Cz6pv1::PAGE_HEIGHT;
+    /// Bytes from here up are commands, below is gradation data.
+    const FIRST_COMMAND: u8 = 0xC0;
+
+    /// Replaces the linear tone curves.
+    pub fn set_calibration(&mut self, calibration: &Calibration) {
+        self.tables = Cz6pv1::tables(calibration);
+    }
+
+    /// Lookup tables from values to ink. Values outside the range of a plane
+    /// are clamped to it.
+    fn tables(calibration: &Calibration) -> [[[u8; 256]; 2]; 3] {
+        std::array::from_fn(|plane| {
+            let (min, max) = Cz6pv1::RANGES[plane];
+            std::array::from_fn(|half| {
+                let offset = if half == 1 { calibration.bottom_half_offset.min(max - min - 1) } else { 0 };
+                std::array::from_fn(|val| {
+                    let level = (val as u8).clamp(min, max) - min;
+                    calibration.ink(plane, level.saturating_sub(offset) as u32, (max - min - offset) as u32 + 1)
+                })
+            })
+        })
+    }
+
+    /// Encodes a canvas as one full frame per page, for the linear tone
+    /// curves. Black ink is printed as all three colours, whatever does not
+    /// fit on the page is left out and the rest of the page gets no ink.
+    pub fn encode(canvas: &Canvas) -> Vec<u8> {
+        let mut data = Vec::new();
+        for page_y in (0..canvas.height()).step_by(Cz6pv1::PAGE_HEIGHT as usize) {
+            data.push(Cz6pv1::FIRST_COMMAND);
+            for (plane, (min, max)) in Cz6pv1::RANGES.into_iter().enumerate() {
+                for y in 0..Cz6pv1::PAGE_HEIGHT {
+                    let offset = if y >= Cz6pv1::SPLIT_ROW { Cz6pv1::BOTTOM_HALF_OFFSET } else { 0 };
+                    let steps = (max - min - offset) as u32;
+                    data.extend((0..Cz6pv1::PAGE_WIDTH).map(|x| {
+                        let ink = if x < canvas.width() && page_y + y < canvas.height() {
+                            canvas.ink(plane as u32 + 1, x, page_y + y).max(canvas.ink(0, x, page_y + y))
+                        } else {
+                            0
+                        };
+                        min + offset + (((255 - ink) as u32 * steps + 127) / 255) as u8
+                    }));
+                }
+            }
+        }
+        data
+    }
+}
+
+impl Decode for Cz6pv1 {
+    type Parser = Parser;
+
+    fn tokens(&mut self) -> &mut Tokenizer<Parser> {
+        &mut self.tokens
+    }
+
+    fn reset(&mut self) {
+        self.tokens = Tokenizer::default();
+        self.line = 0;
+        self.covered_y = 0;
+        self.pages.clear();
+    }
+
+    fn execute(&mut self, _offset: usize, command: &Command, img_mutex: &Mutex<Canvas>) -> Result<(), DecodeError> {
+        match command {
+            // every frame is printed on its own
+            Command::Frame => {
+                self.pages.extend(eject(img_mutex, self.covered(), Cz6pv1::DPI));
+                self.covered_y = 0;
+                self.line = 0;
+            },
+            Command::FrameEnd(_) => {},
+            Command::Line(line) => {
+                let plane = self.line / Cz6pv1::PAGE_HEIGHT;
+                let y = self.line % Cz6pv1::PAGE_HEIGHT;
+                self.draw_line(plane, y, line, img_mutex);
+                self.line += 1;
+                self.covered_y = self.covered_y.max(y + 1);
+            },
+        }
+        Ok(())
+    }
+
+    fn describe(&self, command: &Command) -> Option<String> {
+        match command {
+            Command::Frame => Some(format!("0xC0 full frame, 3 planes of {} lines", Cz6pv1::PAGE_HEIGHT)),
+            Command::Line(_) => None,
+            Command::FrameEnd(lines) => Some(format!("frame cut off after {lines} of {} lines", Cz6pv1::FRAME_LINES)),
+        }
+    }
+}
+
+impl Cz6pv1 {
+    fn draw_line(&self, plane: u32, y: u32, line: &[u8], img_mutex: &Mutex<Canvas>) {
+        let mut img = img_mutex.lock().unwrap();
+        let table = &self.tables[plane as usize][(y >= Cz6pv1::SPLIT_ROW) as usize];
+        for (x, &val) in line.iter().enumerate() {
+            // planes are yellow, magenta and cyan
+            img.set(plane + 1, x as u32, y, table[val as usize]);
+        }
+    }
+}
+
+impl Printer for Cz6pv1 {
+    fn create_image(&self) -> Canvas {
+        Canvas::with_gradation(Cz6pv1::PAGE_WIDTH, Cz6pv1::PAGE_HEIGHT)
+    }
+
+    fn dpi(&self) -> (u32, u32) {
+        Cz6pv1::DPI
+    }
+
+    fn feed_traced(&mut self, data: &[u8], img_mutex: &Mutex<Canvas>, trace: Option<&mut dyn FnMut(Trace)>) -> Result<(), DecodeError> {
+        feed_commands(self, data, img_mutex, trace)
+    }
+
+    fn finish(&mut self) -> Result<(u32, u32), DecodeError> {
+        finish_commands(self)
+    }
+
+    fn covered(&self) -> (u32, u32) {
+        if self.covered_y == 0 {
+            (0, 0)
+        } else {
+            (Cz6pv1::PAGE_WIDTH, self.covered_y)
+        }
+    }
+
+    fn take_pages(&mut self) -> Vec<Page> {
+        std::mem::take(&mut self.pages)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn draw(printer: &Cz6pv1, plane: u32, y: u32, line: &[u8]) -> Vec<u8> {
+        let img_mutex = Mutex::new(printer.create_image());
+        printer.draw_line(plane, y, line, &img_mutex);
+        let img = img_mutex.into_inner().unwrap();
+        (0..line.len() as u32).map(|x| img.ink(plane + 1, x, y)).collect()
+    }
+
+    fn frame(lines: u32, val: u8) -> Vec<u8> {
+        [vec![0xc0], vec![val; (lines * Cz6pv1::PAGE_WIDTH) as usize]].concat()
+    }
+
+    #[test]
+    fn every_frame_is_a_page() {
+        let data = [frame(Cz6pv1::FRAME_LINES, 0x80), frame(Cz6pv1::FRAME_LINES, 0xbf)].concat();
+        let (pages, result) = Cz6pv1::default().decode(&mut &data[..]);
+        assert_eq!(result, Ok(()));
+        assert_eq!(pages.len(), 2);
+        assert!(pages.iter().all(|page| page.covered == (Cz6pv1::PAGE_WIDTH, Cz6pv1::PAGE_HEIGHT)));
+        // 0x80 is full yellow ink, 0xbf no ink in any plane
+        assert_eq!(pages[0].canvas.ink(1, 0, 0), 255);
+        assert_eq!(pages[1].canvas.ink(1, 0, 0), 0);
+    }
+
+    #[test]
+    fn next_command_cuts_a_frame_short() {
+        let data = [&frame(1, 0x80)[..], &[0x80; 100], &frame(Cz6pv1::FRAME_LINES, 0xbf)].concat();
+        let mut printer = Cz6pv1::default();
+        let img_mutex = Mutex::new(printer.create_image());
+        let mut descriptions = Vec::new();
+        let result = printer.feed_traced(&data, &img_mutex, Some(&mut |trace: Trace| descriptions.push(trace.description)));
+        assert_eq!(result, Ok(()));
+        assert_eq!(descriptions[1], format!("frame cut off after 2 of {} lines", Cz6pv1::FRAME_LINES));
+        let pages = printer.take_pages();
+        assert_eq!(pages.len(), 1);
+        assert_eq!(pages[0].covered, (Cz6pv1::PAGE_WIDTH, 2));
+        assert_eq!((pages[0].canvas.ink(1, 99, 1), pages[0].canvas.ink(1, 100, 1)), (255, 0));
+        assert_eq!(printer.finish(), Ok((Cz6pv1::PAGE_WIDTH, Cz6pv1::PAGE_HEIGHT)));
+    }
+
+    #[test]
+    fn values_map_over_the_range_of_each_plane() {
+        let printer = Cz6pv1::default();
+        assert_eq!(draw(&printer, 0, 0, &[0x80, 0xa0, 0xbf, 0x00, 0xff]), [255, 125, 0, 255, 0]);
+        assert_eq!(draw(&printer, 1, 0, &[0x40, 0x7e, 0x7f]), [255, 0, 0]);
+        assert_eq!(draw(&printer, 2, 0, &[0x00, 0x1f, 0x3e]), [255, 128, 0]);
+    }
+
+    #[test]
+    fn bottom_half_is_offset() {
+        let printer = Cz6pv1::default();
+        // values below the offset used to underflow
+        assert_eq!(draw(&printer, 0, 600, &[0x80, 0x86, 0x87, 0xbf]), [255, 255, 255, 0]);
+        assert_eq!(draw(&printer, 2, 511, &[0x07]), [226]);
+    }
+
+    #[test]
+    fn calibration_replaces_the_curves() {
+        let mut printer = Cz6pv1::default();
+        printer.set_calibration(&Calibration::parse("C 200 200\noffset 0").unwrap());
+        assert_eq!(draw(&printer, 2, 600, &[0x00, 0x3e]), [200, 200]);
+        assert_eq!(draw(&printer, 0, 600, &[0x80]), [255]);
+    }
+
+    #[test]
+    fn encoded_canvas_decodes_to_the_same_planes() {
+        let mut canvas = Canvas::with_gradation(600, 1000);
+        for (x, y, color, ink) in [(0, 0, 1, 255), (1, 0, 2, 128), (511, 991, 3, 255), (2, 600, 1, 128), (3, 995, 2, 255)] {
+            canvas.set(color, x, y, ink);
+        }
+        let data = Cz6pv1::encode(&canvas);
+        assert_eq!(data.len(), 2 * (1 + Cz6pv1::FRAME_LINES as usize * Cz6pv1::PAGE_WIDTH as usize));
+        let (pages, result) = Cz6pv1::default().decode(&mut &data[..]);
+        assert_eq!(result, Ok(()));
+        assert_eq!(pages.len(), 2);
+        // the right edge is cut off
+        let expected = canvas.crop(0, 0, Cz6pv1::PAGE_WIDTH, Cz6pv1::PAGE_HEIGHT);
+        assert!((1..4).all(|color| pages[0].canvas.plane_data(color) == expected.plane_data(color)));
+        assert_eq!(pages[1].canvas.ink(2, 3, 3), 255);
+        assert_eq!(pages[1].canvas.ink(2, 3, 8), 0);
+    }
+}
@@ -0,0 +1,596 @@
+use std::sync::Mutex;
+
+use super::font;
+use super::{color_name, describe_data, feed_commands, finish_commands, parse_decimal, Canvas, Decode, DecodeError, Page, Paper, Parse, Parsed, Printer, Tokenizer, Trace, PLANE_NAMES};
+
+/// A single command in a CZ-8PC4 byte stream.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// ESC c
+    Reset,
+    /// ESC # n, meaning unknown
+    Unknown23(u8),
+    /// ESC % nn, line spacing in 1/120 inch
+    LineSpacing(u32),
+    /// ESC 0x19, switches to colour printing, one pass per plane starting
+    /// with yellow
+    ColorMode,
+    /// ESC L nnn, meaning unknown
+    Unknown4c([u8; 3]),
+    /// ESC M nn, 48 dot graphics followed by the given number of columns
+    Graphics(u32),
+    /// One column of graphics data, 6 bytes top to bottom
+    Column([u8; 6]),
+    LineFeed,
+    /// Form feed, starts a new page
+    FormFeed,
+    /// Carriage return, also advances to the next colour pass in colour mode
+    CarriageReturn,
+    /// Any other byte, printable ones are printed as text
+    Data(u8),
+}
+
+#[derive(Default)]
+pub struct Parser {
+    columns_left: u32,
+    start: usize,
+}
+
+impl Parse for Parser {
+    type Command = Command;
+
+    fn parse(&mut self, buf: &[u8], offset: usize) -> Parsed<Command> {
+        if self.columns_left > 0 {
+            let Some(p) = buf.first_chunk::<6>() else {
+                return Parsed::Incomplete;
+            };
+            self.columns_left -= 1;
+            return Parsed::Command(6, Command::Column(*p));
+        }
+        let Some(&c) = buf.first() else {
+            return Parsed::Incomplete;
+        };
+        match c {
+            // escape
+            0x1b => {
+                let Some(&b) = buf.get(1) else {
+                    return Parsed::Incomplete;
+                };
+                let len = match b {
+                    0x23 => 3,
+                    0x25 => 4,
+                    0x4c => 5,
+                    0x4d => 4,
+                    _ => 2,
+                };
+                if buf.len() < len {
+                    return Parsed::Incomplete;
+                }
+                let command = match b {
+                    0x63 => Command::Reset,
+                    0x23 => Command::Unknown23(buf[2]), // unknown 1 byte
+                    0x25 => match parse_decimal(&buf[2..4], offset) {
+                        Ok(spacing) => Command::LineSpacing(spacing),
+                        Err(e) => return Parsed::Invalid(len, e),
+                    },
+                    0x19 => Command::ColorMode,
+                    0x4c => Command::Unknown4c([buf[2], buf[3], buf[4]]),
+                    0x4d => { // 48 dot
+                        let col_count = u16::from_be_bytes([buf[2], buf[3]]) as u32;
+                        self.columns_left = col_count;
+                        self.start = offset;
+                        Command::Graphics(col_count)
+                    },
+                    _ => {
+                        return Parsed::Invalid(2, DecodeError::UnknownCommand { offset, code: b });
+                    },
+                };
+                Parsed::Command(len, command)
+            },
+            0x0a => Parsed::Command(1, Command::LineFeed),
+            0x0c => Parsed::Command(1, Command::FormFeed),
+            0x0d => Parsed::Command(1, Command::CarriageReturn),
+            _ => Parsed::Command(1, Command::Data(c)),
+        }
+    }
+
+    fn in_progress(&self) -> Option<usize> {
+        (self.columns_left > 0).then_some(self.start)
+    }
+}
+
+/// Planes of the colour passes of a line in the order they are printed,
+/// as parsed by `Cz8pc4::parse_pass_order`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PassOrder(Vec<u32>);
+
+pub struct Cz8pc4 {
+    tokens: Tokenizer<Parser>,
+    head_x: u32,
+    head_y: u32,
+    color: u32, // 0 = black, 1 = yellow, 2 = magenta, 3 = cyan
+    color_mode: bool,
+    pass: usize, // index of the current colour pass in the pass order
+    pass_used: bool, // graphics or text were sent in the current pass
+    line_passes: u32, // colour passes finished on the current line
+    cr_advanced: bool, // the last CR moved on to the next colour pass
+    line_spacing: u32, // in dots
+    paper: Paper,
+    forced_pass_order: Option<PassOrder>, // plane order for jobs without ESC 0x19
+    strict: bool,
+}
+
+impl Default for Cz8pc4 {
+    fn default() -> Cz8pc4 {
+        Cz8pc4 {
+            tokens: Tokenizer::default(),
+            head_x: 0,
+            head_y: 0,
+            color: 0,
+            color_mode: false,
+            pass: 0,
+            pass_used: false,
+            line_passes: 0,
+            cr_advanced: false,
+            line_spacing: Cz8pc4::DEFAULT_LINE_SPACING,
+            paper: Paper::new(Cz8pc4::PAGE_HEIGHT, Cz8pc4::BAND_HEIGHT, Cz8pc4::DPI),
+            forced_pass_order: None,
+            strict: false,
+        }
+    }
+}
+
+impl Cz8pc4 {
+    pub const PAGE_WIDTH: u32 = 2988;
+    pub const PAGE_HEIGHT: u32 = 2000;
+    pub const DPI: (u32, u32) = (320, 360);
+    /// Height of a 48 dot graphics band, the most printed in one pass.
+    const BAND_HEIGHT: u32 = 48;
+    /// Line spacing is given in 1/120 inch, the vertical resolution is 360 dpi.
+    const DOTS_PER_LINE_SPACING_UNIT: u32 = 3;
+    /// One 48 dot graphics band, 16/120 inch.
+    const DEFAULT_LINE_SPACING: u32 = 48;
+    /// Text is printed at 10 characters per inch, the horizontal resolution is 320 dpi.
+    const CHAR_PITCH: u32 = 32;
+    /// Enlargement of the character ROM glyphs, giving 25x35 dot characters.
+    const CHAR_SCALE: u32 = 5;
+    /// Colour passes of a line after ESC 0x19.
+    const PASS_ORDER: [u32; 3] = [1, 2, 3];
+
+    /// Forces the planes of the colour passes into `order` (colours as in
+    /// `Canvas`), for software that does not send ESC 0x19 or prints the
+    /// planes in another order. Jobs start in colour mode.
+    pub fn set_pass_order(&mut self, order: PassOrder) {
+        self.forced_pass_order = Some(order);
+        self.color_mode = true;
+        self.start_line();
+    }
+
+    /// Reports lines whose number of colour passes does not match the pass
+    /// order as errors. Decoding carries on as usual.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    /// Parses a pass order such as `ymc`, one plane name per pass.
+    pub fn parse_pass_order(arg: &str) -> Result<PassOrder, String> {
+        let order = arg.chars().map(|c| {
+            PLANE_NAMES.iter().position(|plane| plane.eq_ignore_ascii_case(&c.to_string()))
+                .map(|color| color as u32)
+                .ok_or_else(|| format!("unknown plane {c:?}, expected K, Y, M or C"))
+        }).collect::<Result<Vec<_>, _>>()?;
+        if order.is_empty() {
+            return Err("no planes given".to_string());
+        }
+        Ok(PassOrder(order))
+    }
+
+    /// Encodes a canvas as a job of 48 dot graphics bands, one pixel per
+    /// dot. A canvas with only black ink is printed in mono mode, otherwise
+    /// in colour passes with black made of all three colours. Pages are
+    /// split at the page height.
+    pub fn encode(canvas: &Canvas) -> Vec<u8> {
+        let mono = (1..4).all(|color| canvas.plane_data(color).iter().all(|&ink| ink == 0));
+        let colors: &[u32] = if mono { &[0] } else { &Cz8pc4::PASS_ORDER };
+        let (width, height) = (canvas.width().min(Cz8pc4::PAGE_WIDTH), canvas.height());
+        let inked = |color, x, y| canvas.ink(color, x, y) != 0 || !mono && canvas.ink(0, x, y) != 0;
+        let mut data = Vec::new();
+        if !mono {
+            data.extend(b"\x1b\x19");
+        }
+        for page_y in (0..height).step_by(Cz8pc4::PAGE_HEIGHT as usize) {
+            if page_y > 0 {
+                data.push(0x0c);
+            }
+            let page_end = (page_y + Cz8pc4::PAGE_HEIGHT).min(height);
+            for band_y in (page_y..page_end).step_by(Cz8pc4::BAND_HEIGHT as usize) {
+                let rows = band_y..(band_y + Cz8pc4::BAND_HEIGHT).min(page_end);
+                let columns = colors.iter().map(|&color| {
+                    (0..width).rev().find(|&x| rows.clone().any(|y| inked(color, x, y))).map_or(0, |x| x + 1)
+                }).collect::<Vec<_>>();
+                if columns.iter().any(|&count| count > 0) {
+                    for (pass, (&color, &count)) in colors.iter().zip(&columns).enumerate() {
+                        // every pass sends graphics, even without columns,
+                        // so that the CR moves on to the next colour
+                        if pass > 0 {
+                            data.push(b'\r');
+                        }
+                        data.extend(b"\x1bM");
+                        data.extend((count as u16).to_be_bytes());
+                        for x in 0..count {
+                            let mut column = [0u8; 6];
+                            for y in rows.clone().filter(|&y| inked(color, x, y)) {
+                                let row = y - band_y;
+                                column[row as usize / 8] |= 0x80 >> (row % 8);
+                            }
+                            data.extend(column);
+                        }
+                    }
+                }
+                data.push(b'\n');
+            }
+        }
+        data
+    }
+
+    fn pass_order(&self) -> &[u32] {
+        self.forced_pass_order.as_ref().map_or(&Cz8pc4::PASS_ORDER, |order| &order.0)
+    }
+
+    /// Goes back to the first colour pass.
+    fn start_line(&mut self) {
+        self.pass = 0;
+        self.pass_used = false;
+        self.line_passes = 0;
+        self.cr_advanced = false;
+        self.color = if self.color_mode { self.pass_order()[0] } else { 0 };
+    }
+
+    /// Ends a line at LF or FF, the next one starts with the first colour
+    /// pass again. In strict mode the number of passes is checked.
+    fn end_line(&mut self, offset: usize) -> Result<(), DecodeError> {
+        let passes = self.line_passes + self.pass_used as u32;
+        let expected = self.pass_order().len() as u32;
+        self.start_line();
+        if self.color_mode && self.strict && passes != 0 && passes != expected {
+            return Err(DecodeError::ColorPasses { offset, passes, expected });
+        }
+        Ok(())
+    }
+
+    /// Prints on continuous paper, the image grows up to `max_height` rows
+    /// instead of being split into pages.
+    pub fn set_banner(&mut self, max_height: u32) {
+        self.paper.set_banner(max_height);
+    }
+
+    /// A printer with the head at `(x, y)`, to test the far edges.
+    #[cfg(test)]
+    pub(crate) fn with_head(x: u32, y: u32) -> Cz8pc4 {
+        Cz8pc4 { head_x: x, head_y: y, ..Cz8pc4::default() }
+    }
+}
+
+impl Decode for Cz8pc4 {
+    type Parser = Parser;
+
+    fn tokens(&mut self) -> &mut Tokenizer<Parser> {
+        &mut self.tokens
+    }
+
+    fn reset(&mut self) {
+        self.tokens = Tokenizer::default();
+        self.head_x = 0;
+        self.head_y = 0;
+        self.color_mode = self.forced_pass_order.is_some();
+        self.start_line();
+        self.line_spacing = Cz8pc4::DEFAULT_LINE_SPACING;
+        self.paper.reset();
+    }
+
+    fn execute(&mut self, offset: usize, command: &Command, img_mutex: &Mutex<Canvas>) -> Result<(), DecodeError> {
+        match command {
+            Command::Reset | Command::Unknown23(_) | Command::Unknown4c(_) => {},
+            Command::LineSpacing(spacing) => {
+                self.line_spacing = spacing * Cz8pc4::DOTS_PER_LINE_SPACING_UNIT;
+            },
+            Command::ColorMode => {
+                self.color_mode = true;
+                self.start_line();
+            },
+            Command::Graphics(_) => {
+                self.pass_used = true;
+                if self.head_y >= self.paper.height() {
+                    return Err(DecodeError::OutOfPage { offset, y: self.head_y });
+                }
+            },
+            Command::Column(p) => {
+                let drawn = self.draw_column(offset, p, img_mutex);
+                self.head_x = self.head_x.saturating_add(1);
+                self.paper.covered.0 = self.paper.covered.0.max(self.head_x.min(Cz8pc4::PAGE_WIDTH));
+                return drawn;
+            },
+            Command::LineFeed => {
+                let checked = self.end_line(offset);
+                self.head_x = 0;
+                self.head_y = self.head_y.saturating_add(self.line_spacing);
+                self.head_y = self.paper.line_feed(self.head_y, img_mutex);
+                return checked;
+            },
+            Command::FormFeed => {
+                let checked = self.end_line(offset);
+                self.head_x = 0;
+                self.head_y = self.paper.form_feed(self.head_y, img_mutex);
+                return checked;
+            },
+            Command::Data(c) => {
+                if let Some(glyph) = font::glyph(*c) {
+                    self.pass_used = true;
+                    let drawn = self.draw_char(offset, glyph, img_mutex);
+                    self.head_x = self.head_x.saturating_add(Cz8pc4::CHAR_PITCH);
+                    return drawn;
+                }
+            },
+            // carriage return / colour change, a CR without anything sent
+            // since the last one only returns the head
+            Command::CarriageReturn => {
+                self.head_x = 0;
+                self.cr_advanced = self.color_mode && self.pass_used;
+                if self.cr_advanced {
+                    self.line_passes += 1;
+                    self.pass = (self.pass + 1) % self.pass_order().len();
+                    self.color = self.pass_order()[self.pass];
+                    self.pass_used = false;
+                }
+            },
+        }
+        Ok(())
+    }
+
+    fn describe(&self, command: &Command) -> Option<String> {
+        Some(match command {
+            Command::Reset => "ESC c reset".to_string(),
+            Command::Unknown23(n) => format!("ESC # {n:#04x} (unknown)"),
+            Command::LineSpacing(spacing) => format!("ESC % line spacing {spacing}/120 inch ({} dots)", self.line_spacing),
+            Command::ColorMode => format!("ESC 0x19 colour mode, colour plane {}", color_name(self.color)),
+            Command::Unknown4c(f) => format!("ESC L {:02x?} (unknown)", f),
+            Command::Graphics(col_count) => format!("ESC M 48-dot graphics, {col_count} columns"),
+            Command::Column(_) => return None,
+            Command::LineFeed => format!("LF +{} dots, row {}", self.line_spacing, self.head_y),
+            Command::FormFeed => "FF new page".to_string(),
+            Command::CarriageReturn if self.cr_advanced => format!("CR \u{2192} colour plane {}", color_name(self.color)),
+            Command::CarriageReturn => "CR".to_string(),
+            Command::Data(c) if font::glyph(*c).is_some() => font::describe(*c),
+            Command::Data(c) => describe_data(*c),
+        })
+    }
+}
+
+impl Cz8pc4 {
+    /// Draws a graphics column at the head. Dots past the right edge are
+    /// clipped, inked dots below the bottom are reported as out of the page.
+    fn draw_column(&mut self, offset: usize, p: &[u8; 6], img_mutex: &Mutex<Canvas>) -> Result<(), DecodeError> {
+        let mut img = img_mutex.lock().unwrap();
+        let page_width = img.width();
+        let page_height = img.height();
+        let pixel_x = self.head_x;
+        if pixel_x >= page_width {
+            return Ok(());
+        }
+        let mut result = Ok(());
+        for (i, p_byte) in p.iter().enumerate() {
+            for y in 0..8 {
+                let pixel_y = self.head_y.saturating_add(y + i as u32 * 8);
+                let inked = p_byte >> (7-y) & 1 != 0;
+                if pixel_y >= page_height {
+                    if inked {
+                        result = result.and(Err(DecodeError::OutOfPage { offset, y: pixel_y }));
+                    }
+                    continue;
+                }
+                // a 0 pin leaves what is already on the paper, in mono mode
+                // the whole band still counts as printed
+                if inked {
+                    img.dot(pixel_x, pixel_y, self.color);
+                }
+                if !self.color_mode || inked {
+                    self.paper.covered.1 = self.paper.covered.1.max(pixel_y + 1);
+                }
+            }
+        }
+        result
+    }
+
+    /// Draws a character at the head, clipped like `draw_column`.
+    fn draw_char(&mut self, offset: usize, glyph: &[u8; 7], img_mutex: &Mutex<Canvas>) -> Result<(), DecodeError> {
+        let mut img = img_mutex.lock().unwrap();
+        let page_width = img.width();
+        let page_height = img.height();
+        let mut result = Ok(());
+        for (x, y) in font::dots(glyph, Cz8pc4::CHAR_SCALE, Cz8pc4::CHAR_SCALE) {
+            let pixel_x = self.head_x.saturating_add(x);
+            let pixel_y = self.head_y.saturating_add(y);
+            if pixel_x >= page_width {
+                continue;
+            }
+            if pixel_y >= page_height {
+                result = result.and(Err(DecodeError::OutOfPage { offset, y: pixel_y }));
+                continue;
+            }
+            img.dot(pixel_x, pixel_y, self.color);
+            self.paper.covered.0 = self.paper.covered.0.max(pixel_x + 1);
+            self.paper.covered.1 = self.paper.covered.1.max(pixel_y + 1);
+        }
+        result
+    }
+}
+
+impl Printer for Cz8pc4 {
+    fn create_image(&self) -> Canvas {
+        Canvas::new(Cz8pc4::PAGE_WIDTH, Cz8pc4::PAGE_HEIGHT)
+    }
+
+    fn dpi(&self) -> (u32, u32) {
+        Cz8pc4::DPI
+    }
+
+    fn feed_traced(&mut self, data: &[u8], img_mutex: &Mutex<Canvas>, trace: Option<&mut dyn FnMut(Trace)>) -> Result<(), DecodeError> {
+        feed_commands(self, data, img_mutex, trace)
+    }
+
+    fn finish(&mut self) -> Result<(u32, u32), DecodeError> {
+        finish_commands(self)
+    }
+
+    fn covered(&self) -> (u32, u32) {
+        self.paper.covered
+    }
+
+    fn take_pages(&mut self) -> Vec<Page> {
+        std::mem::take(&mut self.paper.pages)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(data: &[u8]) -> (Canvas, Result<(u32, u32), DecodeError>) {
+        decode_with(&mut Cz8pc4::default(), data)
+    }
+
+    fn decode_with(printer: &mut Cz8pc4, data: &[u8]) -> (Canvas, Result<(u32, u32), DecodeError>) {
+        let img_mutex = Mutex::new(printer.create_image());
+        let result = printer.feed(data, &img_mutex).and(printer.finish());
+        (img_mutex.into_inner().unwrap(), result)
+    }
+
+    /// A single column graphics band with only the top dot set.
+    const DOT: [u8; 10] = [0x1b, 0x4d, 0x00, 0x01, 0x80, 0, 0, 0, 0, 0];
+
+    fn is_black(img: &Canvas, x: u32, y: u32) -> bool {
+        img.ink(0, x, y) == 255
+    }
+
+    #[test]
+    fn line_feed_defaults_to_48_dots() {
+        let (img, result) = decode(&[b"\n".as_slice(), &DOT].concat());
+        assert_eq!(result, Ok((1, 48 + 48)));
+        assert!(is_black(&img, 0, 48));
+    }
+
+    #[test]
+    fn line_spacing_applies_to_line_feed() {
+        let (img, result) = decode(&[b"\x1b%08\n\n".as_slice(), &DOT].concat());
+        assert_eq!(result, Ok((1, 48 + 48)));
+        assert!(is_black(&img, 0, 48));
+        let (img, result) = decode(&[b"\x1b%20\n".as_slice(), &DOT].concat());
+        assert_eq!(result, Ok((1, 60 + 48)));
+        assert!(is_black(&img, 0, 60));
+    }
+
+    #[test]
+    fn zero_line_spacing_overprints() {
+        let (img, result) = decode(&[&DOT, b"\x1b%00\n".as_slice(), &[0x1b, 0x4d, 0x00, 0x02, 0, 0, 0, 0, 0, 0, 0x80, 0, 0, 0, 0, 0]].concat());
+        assert_eq!(result, Ok((2, 48)));
+        // the blank column does not erase the dot under it
+        assert!(is_black(&img, 0, 0));
+        assert!(is_black(&img, 1, 0));
+    }
+
+    #[test]
+    fn text_is_printed_at_character_pitch() {
+        let (img, result) = decode(b"I\xb1");
+        // 'I' has a bar across the top from the second to the fourth dot
+        assert!(!is_black(&img, 0, 0));
+        assert!(is_black(&img, 5, 0));
+        assert!(is_black(&img, 19, 0));
+        assert!(!is_black(&img, 20, 0));
+        // katakana 'a' starts with a full width bar
+        assert!(is_black(&img, 32, 0));
+        assert!(is_black(&img, 32 + 24, 0));
+        assert_eq!(result, Ok((32 + 25, 35)));
+    }
+
+    #[test]
+    fn unprintable_bytes_do_not_advance() {
+        let (img, result) = decode(b"\x00\x80I");
+        assert!(is_black(&img, 5, 0));
+        assert_eq!(result, Ok((20, 35)));
+    }
+
+    #[test]
+    fn bad_line_spacing_is_reported_and_ignored() {
+        let (img, result) = decode(&[b"\x1b%1x\n".as_slice(), &DOT].concat());
+        assert_eq!(result, Err(DecodeError::BadNumericField { offset: 0, field: b"1x".to_vec() }));
+        assert!(is_black(&img, 0, 48));
+    }
+
+    #[test]
+    fn text_across_the_bottom_edge_is_out_of_page() {
+        let (img, result) = decode(&[b"\x1b%99".as_slice(), &[b'\n'; 6], b"\x1b%66\nI"].concat());
+        assert_eq!(result, Err(DecodeError::OutOfPage { offset: 15, y: 2000 }));
+        assert!(is_black(&img, 5, 1980));
+    }
+
+    #[test]
+    fn colour_passes_start_over_on_each_line() {
+        let (img, result) = decode(&[b"\x1b\x19".as_slice(), &DOT, b"\r", &DOT, b"\n", &DOT].concat());
+        assert_eq!(result, Ok((1, 49)));
+        assert_eq!((img.ink(1, 0, 0), img.ink(2, 0, 0)), (255, 255));
+        assert_eq!((img.ink(1, 0, 48), img.ink(2, 0, 48)), (255, 0));
+    }
+
+    #[test]
+    fn plain_carriage_return_keeps_the_colour() {
+        let (img, result) = decode(&[b"\x1b\x19\r".as_slice(), &DOT, b"\r\r", &DOT].concat());
+        assert_eq!(result, Ok((1, 1)));
+        assert_eq!((img.ink(1, 0, 0), img.ink(2, 0, 0), img.ink(3, 0, 0)), (255, 255, 0));
+    }
+
+    #[test]
+    fn strict_mode_reports_missing_passes() {
+        let data = [b"\x1b\x19".as_slice(), &DOT, b"\r", &DOT, b"\n"].concat();
+        assert_eq!(decode(&data).1, Ok((1, 1)));
+        let mut printer = Cz8pc4::default();
+        printer.set_strict(true);
+        assert_eq!(decode_with(&mut printer, &data).1, Err(DecodeError::ColorPasses { offset: 23, passes: 2, expected: 3 }));
+        let data = [b"\x1b\x19".as_slice(), &DOT, b"\r", &DOT, b"\r", &DOT, b"\r\n"].concat();
+        assert_eq!(decode_with(&mut printer, &data).1, Ok((1, 1)));
+    }
+
+    #[test]
+    fn forced_pass_order_starts_in_colour_mode() {
+        assert!(Cz8pc4::parse_pass_order("ymx").is_err());
+        let mut printer = Cz8pc4::default();
+        printer.set_pass_order(Cz8pc4::parse_pass_order("CMy").unwrap());
+        let (img, _) = decode_with(&mut printer, &[&DOT, b"\r".as_slice(), &DOT].concat());
+        assert_eq!((img.ink(3, 0, 0), img.ink(2, 0, 0), img.ink(1, 0, 0), img.ink(0, 0, 0)), (255, 255, 0, 0));
+    }
+
+    #[test]
+    fn encoded_canvas_decodes_to_the_same_planes() {
+        let mut canvas = Canvas::new(60, 2100);
+        for (x, y, color) in [(0, 0, 1), (5, 47, 2), (5, 47, 3), (59, 48, 1), (10, 1999, 3), (20, 2099, 2)] {
+            canvas.dot(x, y, color);
+        }
+        let data = Cz8pc4::encode(&canvas);
+        assert!(data.starts_with(b"\x1b\x19"));
+        let (pages, result) = Cz8pc4::default().decode(&mut &data[..]);
+        assert_eq!(result, Ok(()));
+        assert_eq!(pages.len(), 2);
+        for (page, page_y) in pages.iter().zip([0, 2000]) {
+            let expected = canvas.crop(0, page_y, 60, 2000);
+            let decoded = page.canvas.crop(0, 0, 60, expected.height());
+            assert!((0..4).all(|color| decoded.plane_data(color) == expected.plane_data(color)));
+        }
+        // black only is printed in mono mode
+        let mut canvas = Canvas::new(8, 60);
+        canvas.dot(7, 59, 0);
+        let data = Cz8pc4::encode(&canvas);
+        assert_eq!(&data[..4], b"\n\x1bM\x00");
+        let (img, result) = decode(&data);
+        assert_eq!(result, Ok((8, 48 + 48)));
+        assert!(is_black(&img, 7, 59));
+    }
+}
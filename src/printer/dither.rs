@@ -0,0 +1,148 @@
+use std::fmt;
+use std::str::FromStr;
+use image::RgbImage;
+
+use super::Canvas;
+
+/// How ink amounts are turned into dots for printers without gradation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Dither {
+    /// A dot wherever there is at least half ink.
+    Threshold,
+    /// 8x8 Bayer matrix, a regular pattern that suits flat colours.
+    Ordered,
+    /// Error diffusion, best for photos.
+    #[default]
+    FloydSteinberg,
+}
+
+impl Dither {
+    const NAMES: [(&'static str, Dither); 3] = [
+        ("threshold", Dither::Threshold),
+        ("ordered", Dither::Ordered),
+        ("floyd-steinberg", Dither::FloydSteinberg),
+    ];
+}
+
+impl fmt::Display for Dither {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (name, _) = Dither::NAMES.iter().find(|(_, dither)| dither == self).unwrap();
+        f.write_str(name)
+    }
+}
+
+impl FromStr for Dither {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Dither, String> {
+        Dither::NAMES.iter().find(|(name, _)| *name == s).map(|&(_, dither)| dither)
+            .ok_or_else(|| format!("unknown dither {s:?}, expected threshold, ordered or floyd-steinberg"))
+    }
+}
+
+/// Thresholds of the ordered dither, 0 to 63.
+const BAYER: [[u8; 8]; 8] = [
+    [0, 32, 8, 40, 2, 34, 10, 42],
+    [48, 16, 56, 24, 50, 18, 58, 26],
+    [12, 44, 4, 36, 14, 46, 6, 38],
+    [60, 28, 52, 20, 62, 30, 54, 22],
+    [3, 35, 11, 43, 1, 33, 9, 41],
+    [51, 19, 59, 27, 49, 17, 57, 25],
+    [15, 47, 7, 39, 13, 45, 5, 37],
+    [63, 31, 55, 23, 61, 29, 53, 21],
+];
+
+/// Splits an image into ink planes, one pixel per printer dot. With
+/// `gradation` the amounts of yellow, magenta and cyan are kept, otherwise
+/// black takes over what the three colours have in common and every plane
+/// is dithered to dots.
+pub fn separate(img: &RgbImage, gradation: bool, dither: Dither) -> Canvas {
+    let (width, height) = img.dimensions();
+    let mut amounts: [Vec<f32>; 4] = std::array::from_fn(|_| Vec::with_capacity(width as usize * height as usize));
+    for pixel in img.pixels() {
+        // colours in the order of the planes, yellow absorbs blue and so on
+        let [y, m, c] = [pixel[2], pixel[1], pixel[0]].map(|channel| 1.0 - channel as f32 / 255.0);
+        let k = if gradation { 0.0 } else { y.min(m).min(c) };
+        let under = |amount: f32| if k < 1.0 { (amount - k) / (1.0 - k) } else { 0.0 };
+        for (plane, amount) in amounts.iter_mut().zip([k, under(y), under(m), under(c)]) {
+            plane.push(amount);
+        }
+    }
+    if gradation {
+        let mut canvas = Canvas::with_gradation(width, height);
+        for (color, plane) in amounts.iter().enumerate().skip(1) {
+            for (i, amount) in plane.iter().enumerate() {
+                canvas.set(color as u32, i as u32 % width, i as u32 / width, (amount * 255.0).round() as u8);
+            }
+        }
+        return canvas;
+    }
+    let mut canvas = Canvas::new(width, height);
+    for (color, plane) in amounts.iter_mut().enumerate() {
+        for y in 0..height as usize {
+            for x in 0..width as usize {
+                let amount = plane[y * width as usize + x];
+                let dot = match dither {
+                    Dither::Threshold | Dither::FloydSteinberg => amount >= 0.5,
+                    Dither::Ordered => amount * 64.0 > BAYER[y % 8][x % 8] as f32 + 0.5,
+                };
+                if dot {
+                    canvas.dot(x as u32, y as u32, color as u32);
+                }
+                if dither == Dither::FloydSteinberg {
+                    let error = amount - dot as u32 as f32;
+                    let mut spread = |dx: isize, dy: usize, share: f32| {
+                        let nx = x as isize + dx;
+                        if (0..width as isize).contains(&nx) && y + dy < height as usize {
+                            plane[(y + dy) * width as usize + nx as usize] += error * share;
+                        }
+                    };
+                    spread(1, 0, 7.0 / 16.0);
+                    spread(-1, 1, 3.0 / 16.0);
+                    spread(0, 1, 5.0 / 16.0);
+                    spread(1, 1, 1.0 / 16.0);
+                }
+            }
+        }
+    }
+    canvas
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgb;
+
+    fn inked(canvas: &Canvas, color: u32) -> usize {
+        canvas.plane_data(color).iter().filter(|&&ink| ink != 0).count()
+    }
+
+    #[test]
+    fn black_replaces_what_the_colours_share() {
+        let img = RgbImage::from_fn(2, 1, |x, _| if x == 0 { Rgb([0, 0, 0]) } else { Rgb([0, 255, 0]) });
+        let canvas = separate(&img, false, Dither::Threshold);
+        assert_eq!((canvas.ink(0, 0, 0), canvas.ink(1, 0, 0), canvas.ink(3, 0, 0)), (255, 0, 0));
+        assert_eq!((canvas.ink(0, 1, 0), canvas.ink(1, 1, 0), canvas.ink(2, 1, 0), canvas.ink(3, 1, 0)), (0, 255, 0, 255));
+        let canvas = separate(&img, true, Dither::Threshold);
+        assert_eq!((canvas.ink(1, 0, 0), canvas.ink(2, 0, 0), canvas.ink(3, 0, 0)), (255, 255, 255));
+    }
+
+    #[test]
+    fn dithering_keeps_the_amount_of_ink() {
+        let img = RgbImage::from_pixel(32, 32, Rgb([255, 255, 191]));
+        for dither in [Dither::Ordered, Dither::FloydSteinberg] {
+            let yellow = inked(&separate(&img, false, dither), 1);
+            assert!((240..=272).contains(&yellow), "{dither}: {yellow}");
+        }
+        assert_eq!(inked(&separate(&img, false, Dither::Threshold), 1), 0);
+    }
+
+    #[test]
+    fn names_round_trip() {
+        for (name, dither) in Dither::NAMES {
+            assert_eq!(name.parse(), Ok(dither));
+            assert_eq!(dither.to_string(), name);
+        }
+        assert!("atkinson".parse::<Dither>().is_err());
+    }
+}
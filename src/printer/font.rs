@@ -0,0 +1,366 @@
+//! Character fonts for text printing: the built-in 5x7 dot ROM for ANK
+//! (JIS X 0201) text and 24x24 dot kanji (JIS X 0208) fonts loaded from BDF files.
+
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+
+pub const GLYPH_WIDTH: u32 = 5;
+pub const GLYPH_HEIGHT: u32 = 7;
+
+/// Returns the glyph for a JIS X 0201 character, one byte per row from top to
+/// bottom with the leftmost dot in bit 4.
+pub fn glyph(c: u8) -> Option<&'static [u8; 7]> {
+    match c {
+        0x20..=0x7e => Some(&ASCII[(c - 0x20) as usize]),
+        0xa1..=0xdf => Some(&KATAKANA[(c - 0xa1) as usize]),
+        _ => None,
+    }
+}
+
+/// Iterates over the set dots of a glyph enlarged by `scale_x` and `scale_y`,
+/// as offsets from its top left corner.
+pub fn dots(glyph: &[u8; 7], scale_x: u32, scale_y: u32) -> impl Iterator<Item = (u32, u32)> + '_ {
+    (0..GLYPH_HEIGHT * scale_y).flat_map(move |y| {
+        let row = glyph[(y / scale_y) as usize];
+        (0..GLYPH_WIDTH * scale_x)
+            .filter(move |x| row >> (GLYPH_WIDTH - 1 - x / scale_x) & 1 != 0)
+            .map(move |x| (x, y))
+    })
+}
+
+/// Describes a character for the disassembler.
+pub fn describe(c: u8) -> String {
+    match c {
+        0x5c => "character '\u{a5}'".to_string(),
+        0x7e => "character '\u{203e}'".to_string(),
+        0x20..=0x7e => format!("character {:?}", c as char),
+        0xa1..=0xdf => format!("character '{}'", char::from_u32(0xff61 + (c - 0xa1) as u32).unwrap()),
+        _ => unreachable!(),
+    }
+}
+
+pub const KANJI_SIZE: u32 = 24;
+
+/// 24x24 dot kanji glyphs indexed by JIS X 0208 code, e.g. from jiskan24.bdf.
+/// Each row has the leftmost dot in bit 23.
+#[derive(Clone)]
+pub struct KanjiFont {
+    glyphs: HashMap<u16, [u32; 24]>,
+}
+
+impl KanjiFont {
+    pub fn load(path: &Path) -> io::Result<KanjiFont> {
+        KanjiFont::parse_bdf(&std::fs::read_to_string(path)?)
+    }
+
+    /// Parses a BDF font whose ENCODING values are JIS codes. Glyphs are placed
+    /// on the font baseline and clipped to 24x24 dots.
+    pub fn parse_bdf(bdf: &str) -> io::Result<KanjiFont> {
+        let invalid = |line: &str| io::Error::new(io::ErrorKind::InvalidData, format!("bad BDF line {line:?}"));
+        let numbers = |line: &str| -> io::Result<Vec<i32>> {
+            line.split_whitespace().skip(1).map(|n| n.parse().map_err(|_| invalid(line))).collect()
+        };
+        let mut glyphs = HashMap::new();
+        let mut ascent = KANJI_SIZE as i32;
+        let mut encoding = None;
+        let mut bbx = [KANJI_SIZE as i32, KANJI_SIZE as i32, 0, 0];
+        let mut lines = bdf.lines();
+        while let Some(line) = lines.next() {
+            match line.split_whitespace().next() {
+                Some("FONTBOUNDINGBOX") => {
+                    let fbb = numbers(line)?;
+                    let [_, h, _, yoff] = fbb[..] else {
+                        return Err(invalid(line));
+                    };
+                    ascent = h.checked_add(yoff).ok_or_else(|| invalid(line))?;
+                },
+                Some("FONT_ASCENT") => {
+                    ascent = *numbers(line)?.first().ok_or_else(|| invalid(line))?;
+                },
+                Some("STARTCHAR") => {
+                    encoding = None;
+                },
+                Some("ENCODING") => {
+                    let code = *numbers(line)?.first().ok_or_else(|| invalid(line))?;
+                    encoding = u16::try_from(code).ok();
+                },
+                Some("BBX") => {
+                    bbx = numbers(line)?.try_into().map_err(|_| invalid(line))?;
+                },
+                Some("BITMAP") => {
+                    let [w, h, xoff, yoff] = bbx;
+                    let top = h.checked_add(yoff).and_then(|bottom| ascent.checked_sub(bottom)).ok_or_else(|| invalid(line))?;
+                    let mut rows = [0u32; 24];
+                    for i in 0..h {
+                        let hex = lines.next().ok_or_else(|| invalid(line))?.trim();
+                        // rows wider than 64 dots do not fit in the shift below
+                        if hex.len() > 16 {
+                            return Err(invalid(hex));
+                        }
+                        let bits = u64::from_str_radix(hex, 16).map_err(|_| invalid(hex))?;
+                        let bit_count = hex.len() as i32 * 4;
+                        let Some(y) = top.checked_add(i).filter(|y| (0..KANJI_SIZE as i32).contains(y)) else {
+                            continue;
+                        };
+                        for j in 0..w.min(bit_count) {
+                            let on_glyph = xoff.checked_add(j).filter(|x| (0..KANJI_SIZE as i32).contains(x));
+                            if let Some(x) = on_glyph && bits >> (bit_count - 1 - j) & 1 != 0 {
+                                rows[y as usize] |= 1 << (KANJI_SIZE as i32 - 1 - x);
+                            }
+                        }
+                    }
+                    if let Some(code) = encoding {
+                        glyphs.insert(code, rows);
+                    }
+                },
+                _ => {},
+            }
+        }
+        Ok(KanjiFont { glyphs })
+    }
+
+    pub fn glyph(&self, code: u16) -> Option<&[u32; 24]> {
+        self.glyphs.get(&code)
+    }
+}
+
+/// Iterates over the set dots of a kanji glyph, as offsets from its top left corner.
+pub fn kanji_dots(glyph: &[u32; 24]) -> impl Iterator<Item = (u32, u32)> + '_ {
+    (0..KANJI_SIZE).flat_map(move |y| {
+        (0..KANJI_SIZE)
+            .filter(move |x| glyph[y as usize] >> (KANJI_SIZE - 1 - x) & 1 != 0)
+            .map(move |x| (x, y))
+    })
+}
+
+/// Outline box printed in place of kanji missing from the font.
+pub const MISSING_KANJI: [u32; 24] = {
+    let mut rows = [0x400002; 24];
+    rows[0] = 0;
+    rows[1] = 0x7ffffe;
+    rows[22] = 0x7ffffe;
+    rows[23] = 0;
+    rows
+};
+
+const ASCII: [[u8; 7]; 95] = [
+    [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000], // 0x20 ' '
+    [0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00000, 0b00100], // 0x21 '!'
+    [0b01010, 0b01010, 0b01010, 0b00000, 0b00000, 0b00000, 0b00000], // 0x22 '"'
+    [0b01010, 0b01010, 0b11111, 0b01010, 0b11111, 0b01010, 0b01010], // 0x23 '#'
+    [0b00100, 0b01111, 0b10100, 0b01110, 0b00101, 0b11110, 0b00100], // 0x24 '$'
+    [0b11000, 0b11001, 0b00010, 0b00100, 0b01000, 0b10011, 0b00011], // 0x25 '%'
+    [0b01100, 0b10010, 0b10100, 0b01000, 0b10101, 0b10010, 0b01101], // 0x26 '&'
+    [0b01100, 0b00100, 0b01000, 0b00000, 0b00000, 0b00000, 0b00000], // 0x27 '\''
+    [0b00010, 0b00100, 0b01000, 0b01000, 0b01000, 0b00100, 0b00010], // 0x28 '('
+    [0b01000, 0b00100, 0b00010, 0b00010, 0b00010, 0b00100, 0b01000], // 0x29 ')'
+    [0b00000, 0b00100, 0b10101, 0b01110, 0b10101, 0b00100, 0b00000], // 0x2a '*'
+    [0b00000, 0b00100, 0b00100, 0b11111, 0b00100, 0b00100, 0b00000], // 0x2b '+'
+    [0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b00100, 0b01000], // 0x2c ','
+    [0b00000, 0b00000, 0b00000, 0b11111, 0b00000, 0b00000, 0b00000], // 0x2d '-'
+    [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b01100], // 0x2e '.'
+    [0b00000, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b00000], // 0x2f '/'
+    [0b01110, 0b10001, 0b10011, 0b10101, 0b11001, 0b10001, 0b01110], // 0x30 '0'
+    [0b00100, 0b01100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110], // 0x31 '1'
+    [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b01000, 0b11111], // 0x32 '2'
+    [0b11111, 0b00010, 0b00100, 0b00010, 0b00001, 0b10001, 0b01110], // 0x33 '3'
+    [0b00010, 0b00110, 0b01010, 0b10010, 0b11111, 0b00010, 0b00010], // 0x34 '4'
+    [0b11111, 0b10000, 0b11110, 0b00001, 0b00001, 0b10001, 0b01110], // 0x35 '5'
+    [0b00110, 0b01000, 0b10000, 0b11110, 0b10001, 0b10001, 0b01110], // 0x36 '6'
+    [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b01000, 0b01000], // 0x37 '7'
+    [0b01110, 0b10001, 0b10001, 0b01110, 0b10001, 0b10001, 0b01110], // 0x38 '8'
+    [0b01110, 0b10001, 0b10001, 0b01111, 0b00001, 0b00010, 0b01100], // 0x39 '9'
+    [0b00000, 0b01100, 0b01100, 0b00000, 0b01100, 0b01100, 0b00000], // 0x3a ':'
+    [0b00000, 0b01100, 0b01100, 0b00000, 0b01100, 0b00100, 0b01000], // 0x3b ';'
+    [0b00010, 0b00100, 0b01000, 0b10000, 0b01000, 0b00100, 0b00010], // 0x3c '<'
+    [0b00000, 0b00000, 0b11111, 0b00000, 0b11111, 0b00000, 0b00000], // 0x3d '='
+    [0b01000, 0b00100, 0b00010, 0b00001, 0b00010, 0b00100, 0b01000], // 0x3e '>'
+    [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b00000, 0b00100], // 0x3f '?'
+    [0b01110, 0b10001, 0b00001, 0b01101, 0b10101, 0b10101, 0b01110], // 0x40 '@'
+    [0b01110, 0b10001, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001], // 0x41 'A'
+    [0b11110, 0b10001, 0b10001, 0b11110, 0b10001, 0b10001, 0b11110], // 0x42 'B'
+    [0b01110, 0b10001, 0b10000, 0b10000, 0b10000, 0b10001, 0b01110], // 0x43 'C'
+    [0b11100, 0b10010, 0b10001, 0b10001, 0b10001, 0b10010, 0b11100], // 0x44 'D'
+    [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b11111], // 0x45 'E'
+    [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b10000], // 0x46 'F'
+    [0b01110, 0b10001, 0b10000, 0b10111, 0b10001, 0b10001, 0b01111], // 0x47 'G'
+    [0b10001, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001], // 0x48 'H'
+    [0b01110, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110], // 0x49 'I'
+    [0b00111, 0b00010, 0b00010, 0b00010, 0b00010, 0b10010, 0b01100], // 0x4a 'J'
+    [0b10001, 0b10010, 0b10100, 0b11000, 0b10100, 0b10010, 0b10001], // 0x4b 'K'
+    [0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b11111], // 0x4c 'L'
+    [0b10001, 0b11011, 0b10101, 0b10101, 0b10001, 0b10001, 0b10001], // 0x4d 'M'
+    [0b10001, 0b10001, 0b11001, 0b10101, 0b10011, 0b10001, 0b10001], // 0x4e 'N'
+    [0b01110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110], // 0x4f 'O'
+    [0b11110, 0b10001, 0b10001, 0b11110, 0b10000, 0b10000, 0b10000], // 0x50 'P'
+    [0b01110, 0b10001, 0b10001, 0b10001, 0b10101, 0b10010, 0b01101], // 0x51 'Q'
+    [0b11110, 0b10001, 0b10001, 0b11110, 0b10100, 0b10010, 0b10001], // 0x52 'R'
+    [0b01111, 0b10000, 0b10000, 0b01110, 0b00001, 0b00001, 0b11110], // 0x53 'S'
+    [0b11111, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100], // 0x54 'T'
+    [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110], // 0x55 'U'
+    [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01010, 0b00100], // 0x56 'V'
+    [0b10001, 0b10001, 0b10001, 0b10101, 0b10101, 0b10101, 0b01010], // 0x57 'W'
+    [0b10001, 0b10001, 0b01010, 0b00100, 0b01010, 0b10001, 0b10001], // 0x58 'X'
+    [0b10001, 0b10001, 0b10001, 0b01010, 0b00100, 0b00100, 0b00100], // 0x59 'Y'
+    [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b11111], // 0x5a 'Z'
+    [0b01110, 0b01000, 0b01000, 0b01000, 0b01000, 0b01000, 0b01110], // 0x5b '['
+    [0b10001, 0b01010, 0b11111, 0b00100, 0b11111, 0b00100, 0b00100], // 0x5c '¥'
+    [0b01110, 0b00010, 0b00010, 0b00010, 0b00010, 0b00010, 0b01110], // 0x5d ']'
+    [0b00100, 0b01010, 0b10001, 0b00000, 0b00000, 0b00000, 0b00000], // 0x5e '^'
+    [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b11111], // 0x5f '_'
+    [0b01000, 0b00100, 0b00010, 0b00000, 0b00000, 0b00000, 0b00000], // 0x60 '`'
+    [0b00000, 0b00000, 0b01110, 0b00001, 0b01111, 0b10001, 0b01111], // 0x61 'a'
+    [0b10000, 0b10000, 0b10110, 0b11001, 0b10001, 0b10001, 0b11110], // 0x62 'b'
+    [0b00000, 0b00000, 0b01110, 0b10000, 0b10000, 0b10001, 0b01110], // 0x63 'c'
+    [0b00001, 0b00001, 0b01101, 0b10011, 0b10001, 0b10001, 0b01111], // 0x64 'd'
+    [0b00000, 0b00000, 0b01110, 0b10001, 0b11111, 0b10000, 0b01110], // 0x65 'e'
+    [0b00110, 0b01001, 0b01000, 0b11100, 0b01000, 0b01000, 0b01000], // 0x66 'f'
+    [0b00000, 0b01111, 0b10001, 0b10001, 0b01111, 0b00001, 0b01110], // 0x67 'g'
+    [0b10000, 0b10000, 0b10110, 0b11001, 0b10001, 0b10001, 0b10001], // 0x68 'h'
+    [0b00100, 0b00000, 0b01100, 0b00100, 0b00100, 0b00100, 0b01110], // 0x69 'i'
+    [0b00010, 0b00000, 0b00110, 0b00010, 0b00010, 0b10010, 0b01100], // 0x6a 'j'
+    [0b10000, 0b10000, 0b10010, 0b10100, 0b11000, 0b10100, 0b10010], // 0x6b 'k'
+    [0b01100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110], // 0x6c 'l'
+    [0b00000, 0b00000, 0b11010, 0b10101, 0b10101, 0b10001, 0b10001], // 0x6d 'm'
+    [0b00000, 0b00000, 0b10110, 0b11001, 0b10001, 0b10001, 0b10001], // 0x6e 'n'
+    [0b00000, 0b00000, 0b01110, 0b10001, 0b10001, 0b10001, 0b01110], // 0x6f 'o'
+    [0b00000, 0b00000, 0b11110, 0b10001, 0b11110, 0b10000, 0b10000], // 0x70 'p'
+    [0b00000, 0b00000, 0b01101, 0b10011, 0b01111, 0b00001, 0b00001], // 0x71 'q'
+    [0b00000, 0b00000, 0b10110, 0b11001, 0b10000, 0b10000, 0b10000], // 0x72 'r'
+    [0b00000, 0b00000, 0b01110, 0b10000, 0b01110, 0b00001, 0b11110], // 0x73 's'
+    [0b01000, 0b01000, 0b11100, 0b01000, 0b01000, 0b01001, 0b00110], // 0x74 't'
+    [0b00000, 0b00000, 0b10001, 0b10001, 0b10001, 0b10011, 0b01101], // 0x75 'u'
+    [0b00000, 0b00000, 0b10001, 0b10001, 0b10001, 0b01010, 0b00100], // 0x76 'v'
+    [0b00000, 0b00000, 0b10001, 0b10001, 0b10101, 0b10101, 0b01010], // 0x77 'w'
+    [0b00000, 0b00000, 0b10001, 0b01010, 0b00100, 0b01010, 0b10001], // 0x78 'x'
+    [0b00000, 0b00000, 0b10001, 0b10001, 0b01111, 0b00001, 0b01110], // 0x79 'y'
+    [0b00000, 0b00000, 0b11111, 0b00010, 0b00100, 0b01000, 0b11111], // 0x7a 'z'
+    [0b00010, 0b00100, 0b00100, 0b01000, 0b00100, 0b00100, 0b00010], // 0x7b '{'
+    [0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100], // 0x7c '|'
+    [0b01000, 0b00100, 0b00100, 0b00010, 0b00100, 0b00100, 0b01000], // 0x7d '}'
+    [0b11111, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000], // 0x7e '‾'
+];
+
+const KATAKANA: [[u8; 7]; 63] = [
+    [0b00000, 0b00000, 0b00000, 0b00000, 0b11100, 0b10100, 0b11100], // 0xa1 ｡
+    [0b11100, 0b10000, 0b10000, 0b10000, 0b00000, 0b00000, 0b00000], // 0xa2 ｢
+    [0b00000, 0b00000, 0b00000, 0b00100, 0b00100, 0b00100, 0b11100], // 0xa3 ｣
+    [0b00000, 0b00000, 0b00000, 0b00000, 0b10000, 0b01000, 0b00100], // 0xa4 ､
+    [0b00000, 0b00000, 0b00000, 0b01100, 0b01100, 0b00000, 0b00000], // 0xa5 ･
+    [0b00000, 0b11111, 0b00001, 0b11111, 0b00001, 0b00010, 0b01100], // 0xa6 ｦ
+    [0b00000, 0b00000, 0b11111, 0b00001, 0b00110, 0b00100, 0b01000], // 0xa7 ｧ
+    [0b00000, 0b00000, 0b00010, 0b00100, 0b01100, 0b10100, 0b00100], // 0xa8 ｨ
+    [0b00000, 0b00000, 0b00100, 0b11111, 0b10001, 0b00001, 0b00110], // 0xa9 ｩ
+    [0b00000, 0b00000, 0b00000, 0b11111, 0b00100, 0b00100, 0b11111], // 0xaa ｪ
+    [0b00000, 0b00000, 0b00010, 0b11111, 0b00110, 0b01010, 0b10010], // 0xab ｫ
+    [0b00000, 0b00000, 0b01000, 0b11111, 0b01001, 0b01010, 0b01000], // 0xac ｬ
+    [0b00000, 0b00000, 0b00000, 0b01110, 0b00010, 0b00010, 0b11111], // 0xad ｭ
+    [0b00000, 0b00000, 0b11110, 0b00010, 0b11110, 0b00010, 0b11110], // 0xae ｮ
+    [0b00000, 0b00000, 0b00000, 0b10101, 0b10101, 0b00001, 0b00110], // 0xaf ｯ
+    [0b00000, 0b00000, 0b00000, 0b11111, 0b00000, 0b00000, 0b00000], // 0xb0 ｰ
+    [0b11111, 0b00001, 0b01010, 0b01100, 0b01000, 0b01000, 0b10000], // 0xb1 ｱ
+    [0b00001, 0b00010, 0b00100, 0b01100, 0b10100, 0b00100, 0b00100], // 0xb2 ｲ
+    [0b00100, 0b11111, 0b10001, 0b10001, 0b00001, 0b00010, 0b00100], // 0xb3 ｳ
+    [0b00000, 0b11111, 0b00100, 0b00100, 0b00100, 0b00100, 0b11111], // 0xb4 ｴ
+    [0b00010, 0b11111, 0b00010, 0b00110, 0b01010, 0b10010, 0b00010], // 0xb5 ｵ
+    [0b01000, 0b11111, 0b01001, 0b01001, 0b01001, 0b01001, 0b10010], // 0xb6 ｶ
+    [0b00100, 0b11111, 0b00100, 0b11111, 0b00100, 0b00100, 0b00100], // 0xb7 ｷ
+    [0b01111, 0b01001, 0b10001, 0b00001, 0b00010, 0b00100, 0b11000], // 0xb8 ｸ
+    [0b01000, 0b01111, 0b10010, 0b00010, 0b00010, 0b00010, 0b00100], // 0xb9 ｹ
+    [0b00000, 0b11111, 0b00001, 0b00001, 0b00001, 0b00001, 0b11111], // 0xba ｺ
+    [0b01010, 0b11111, 0b01010, 0b01010, 0b00010, 0b00100, 0b01000], // 0xbb ｻ
+    [0b00000, 0b11000, 0b00001, 0b11001, 0b00001, 0b00010, 0b11100], // 0xbc ｼ
+    [0b00000, 0b11111, 0b00001, 0b00010, 0b00100, 0b01010, 0b10001], // 0xbd ｽ
+    [0b01000, 0b11111, 0b01001, 0b01010, 0b01000, 0b01000, 0b00111], // 0xbe ｾ
+    [0b00000, 0b10001, 0b10001, 0b01001, 0b00001, 0b00010, 0b01100], // 0xbf ｿ
+    [0b01111, 0b01001, 0b10101, 0b00010, 0b00010, 0b00100, 0b11000], // 0xc0 ﾀ
+    [0b00010, 0b11100, 0b00100, 0b11111, 0b00100, 0b00100, 0b01000], // 0xc1 ﾁ
+    [0b00000, 0b10101, 0b10101, 0b10101, 0b00001, 0b00010, 0b00100], // 0xc2 ﾂ
+    [0b01110, 0b00000, 0b11111, 0b00100, 0b00100, 0b00100, 0b01000], // 0xc3 ﾃ
+    [0b01000, 0b01000, 0b01000, 0b01100, 0b01010, 0b01000, 0b01000], // 0xc4 ﾄ
+    [0b00100, 0b00100, 0b11111, 0b00100, 0b00100, 0b01000, 0b10000], // 0xc5 ﾅ
+    [0b00000, 0b01110, 0b00000, 0b00000, 0b00000, 0b00000, 0b11111], // 0xc6 ﾆ
+    [0b00000, 0b11111, 0b00001, 0b01010, 0b00100, 0b01010, 0b10000], // 0xc7 ﾇ
+    [0b00100, 0b11111, 0b00010, 0b00100, 0b01110, 0b10101, 0b00100], // 0xc8 ﾈ
+    [0b00010, 0b00010, 0b00010, 0b00010, 0b00100, 0b01000, 0b10000], // 0xc9 ﾉ
+    [0b00000, 0b00100, 0b00010, 0b10001, 0b10001, 0b10001, 0b10001], // 0xca ﾊ
+    [0b10000, 0b10000, 0b11111, 0b10000, 0b10000, 0b10000, 0b01111], // 0xcb ﾋ
+    [0b00000, 0b11111, 0b00001, 0b00001, 0b00001, 0b00010, 0b01100], // 0xcc ﾌ
+    [0b00000, 0b01000, 0b10100, 0b00010, 0b00001, 0b00001, 0b00000], // 0xcd ﾍ
+    [0b00100, 0b11111, 0b00100, 0b00100, 0b10101, 0b10101, 0b00100], // 0xce ﾎ
+    [0b00000, 0b11111, 0b00001, 0b00001, 0b01010, 0b00100, 0b00010], // 0xcf ﾏ
+    [0b00000, 0b01110, 0b00000, 0b01110, 0b00000, 0b01110, 0b00001], // 0xd0 ﾐ
+    [0b00000, 0b00100, 0b01000, 0b10000, 0b10001, 0b11111, 0b00001], // 0xd1 ﾑ
+    [0b00000, 0b00001, 0b00001, 0b01010, 0b00100, 0b01010, 0b10000], // 0xd2 ﾒ
+    [0b00000, 0b11111, 0b01000, 0b11111, 0b01000, 0b01000, 0b00111], // 0xd3 ﾓ
+    [0b01000, 0b01000, 0b11111, 0b01001, 0b01010, 0b01000, 0b01000], // 0xd4 ﾔ
+    [0b00000, 0b01110, 0b00010, 0b00010, 0b00010, 0b00010, 0b11111], // 0xd5 ﾕ
+    [0b00000, 0b11111, 0b00001, 0b11111, 0b00001, 0b00001, 0b11111], // 0xd6 ﾖ
+    [0b01110, 0b00000, 0b11111, 0b00001, 0b00001, 0b00010, 0b00100], // 0xd7 ﾗ
+    [0b10010, 0b10010, 0b10010, 0b10010, 0b00010, 0b00100, 0b01000], // 0xd8 ﾘ
+    [0b00000, 0b00100, 0b10100, 0b10100, 0b10101, 0b10101, 0b10110], // 0xd9 ﾙ
+    [0b00000, 0b10000, 0b10000, 0b10001, 0b10010, 0b10100, 0b11000], // 0xda ﾚ
+    [0b00000, 0b11111, 0b10001, 0b10001, 0b10001, 0b10001, 0b11111], // 0xdb ﾛ
+    [0b00000, 0b11111, 0b10001, 0b10001, 0b00001, 0b00010, 0b00100], // 0xdc ﾜ
+    [0b00000, 0b11000, 0b00001, 0b00001, 0b00001, 0b00010, 0b11100], // 0xdd ﾝ
+    [0b10100, 0b01010, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000], // 0xde ﾞ
+    [0b11100, 0b10100, 0b11100, 0b00000, 0b00000, 0b00000, 0b00000], // 0xdf ﾟ
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bdf_glyphs_are_placed_on_the_baseline() {
+        let bdf = "STARTFONT 2.1
+FONTBOUNDINGBOX 24 24 0 -2
+STARTPROPERTIES 1
+FONT_ASCENT 22
+ENDPROPERTIES
+CHARS 1
+STARTCHAR 3021
+ENCODING 12321
+BBX 8 2 4 0
+BITMAP
+FF
+81
+ENDCHAR
+ENDFONT
+";
+        let font = KanjiFont::parse_bdf(bdf).unwrap();
+        let glyph = font.glyph(0x3021).unwrap();
+        assert_eq!(glyph[20], 0x0ff000);
+        assert_eq!(glyph[21], 0x081000);
+        assert_eq!(glyph.iter().filter(|&&row| row != 0).count(), 2);
+        assert!(font.glyph(0x3022).is_none());
+    }
+
+    #[test]
+    fn bad_bdf_is_an_error() {
+        assert!(KanjiFont::parse_bdf("ENCODING 12321\nBBX 8 1 0 0\nBITMAP\nXY\n").is_err());
+    }
+
+    #[test]
+    fn rows_wider_than_64_dots_are_an_error() {
+        let bdf = "ENCODING 12321\nBBX 72 1 0 0\nBITMAP\nFFFFFFFFFFFFFFFFFF\n";
+        assert_eq!(KanjiFont::parse_bdf(bdf).err().unwrap().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn huge_bounding_boxes_are_an_error() {
+        let fbb = format!("FONTBOUNDINGBOX 24 {} 0 1\n", i32::MAX);
+        assert_eq!(KanjiFont::parse_bdf(&fbb).err().unwrap().kind(), io::ErrorKind::InvalidData);
+        let bbx = format!("FONT_ASCENT {}\nENCODING 12321\nBBX 8 1 0 0\nBITMAP\nFF\n", i32::MIN);
+        assert_eq!(KanjiFont::parse_bdf(&bbx).err().unwrap().kind(), io::ErrorKind::InvalidData);
+        let bbx = format!("ENCODING 12321\nBBX 8 1 0 {}\nBITMAP\nFF\n", i32::MAX);
+        assert_eq!(KanjiFont::parse_bdf(&bbx).err().unwrap().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn glyphs_far_off_the_grid_are_clipped() {
+        let bdf = format!("ENCODING 12321\nBBX 8 1 {} 0\nBITMAP\nFF\n", i32::MAX - 2);
+        assert_eq!(KanjiFont::parse_bdf(&bdf).unwrap().glyph(12321), Some(&[0; 24]));
+    }
+}
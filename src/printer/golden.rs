@@ -0,0 +1,109 @@
+//! Golden tests: small synthetic jobs for every model, decoded and checked
+//! against hashes of the ink on each page. A protocol change that moves a
+//! single dot shows up here.
+//!
+//! After an intended change in rendering, run the tests and copy the actual
+//! values printed for the failing cases into `cases`.
+
+use image::{Rgb, RgbImage};
+
+use super::{find_model, separate, Canvas, Dither, Page, PrinterOptions};
+
+/// What decoding a job should give.
+struct Expected {
+    /// Covered width and height and ink hash of every page.
+    pages: &'static [((u32, u32), u64)],
+    /// The decode error, as displayed.
+    error: Option<&'static str>,
+}
+
+/// FNV-1a over the size and the planes of a canvas, stable across platforms
+/// and compiler versions.
+fn hash(canvas: &Canvas) -> u64 {
+    let size = [canvas.width().to_le_bytes(), canvas.height().to_le_bytes()].concat();
+    let planes = (0..4).flat_map(|color| canvas.plane_data(color).iter().copied());
+    size.into_iter().chain(planes).fold(0xcbf2_9ce4_8422_2325, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100_0000_01b3))
+}
+
+/// Diagonal rows of 8 dot squares in the three colours and black, with
+/// white margins.
+fn pattern(width: u32, height: u32) -> RgbImage {
+    const COLORS: [Rgb<u8>; 4] = [Rgb([255, 255, 0]), Rgb([255, 0, 255]), Rgb([0, 255, 255]), Rgb([0, 0, 0])];
+    RgbImage::from_fn(width, height, |x, y| {
+        if x < 4 || y < 4 || x >= width - 4 || y >= height - 4 {
+            Rgb([255, 255, 255])
+        } else {
+            COLORS[((x / 8 + y / 8) % 4) as usize]
+        }
+    })
+}
+
+/// A job for a model that does not decode what it encodes is a bug too.
+fn encoded(model: &str, gradation: bool) -> Vec<u8> {
+    let canvas = separate(&pattern(64, 64), gradation, Dither::Threshold);
+    (find_model(model).unwrap().encode.unwrap())(&canvas)
+}
+
+fn cases() -> Vec<(&'static str, &'static str, Vec<u8>, Expected)> {
+    let cz6pv1_frame = |lines: usize| {
+        let line = |plane: usize| (0..512).map(|x| [0x80, 0x40, 0x00][plane] + (x / 9) as u8).collect::<Vec<_>>();
+        [vec![0xc0], (0..lines).flat_map(|i| line(i / 992)).collect()].concat()
+    };
+    vec![
+        ("cz-8pc4", "mono band", [b"\x1bM\x00\x03".as_slice(), &[0xff, 0, 0, 0, 0, 0x01], &[0x80, 0x40, 0x20, 0x10, 0x08, 0x04], &[0, 0, 0, 0, 0, 0x03]].concat(),
+            Expected { pages: &[((3, 48), 6030854517322900150)], error: None }),
+        ("cz-8pc4", "colour passes", [b"\x1b\x19\x1bM\x00\x01".as_slice(), &[0xf0; 6], b"\r\x1bM\x00\x02", &[0x0f; 12], b"\r\x1bM\x00\x01", &[0xff; 6], b"\r\n\x1bM\x00\x01", &[0x80, 0, 0, 0, 0, 0]].concat(),
+            Expected { pages: &[((2, 49), 15929666970552207890)], error: None }),
+        ("cz-8pc4", "line spacing", [b"\x1b%20\n\x1bM\x00\x01".as_slice(), &[0x80, 0, 0, 0, 0, 1], b"\x1b%01\n\x1bM\x00\x01", &[0x80, 0, 0, 0, 0, 1]].concat(),
+            Expected { pages: &[((1, 63 + 48), 2542284854252437853)], error: None }),
+        ("cz-8pc4", "clipped at the right edge", [b"\x1bM\x0b\xb8".as_slice(), &[0xff; 3000 * 6]].concat(),
+            Expected { pages: &[((2988, 48), 15308497266213475581)], error: None }),
+        ("cz-8pc4", "band across the bottom edge", [b"\x1b%99".as_slice(), &[b'\n'; 6], b"\x1b%60\n\x1bM\x00\x01", &[0xff; 6], b"\x1b%16\n\x1bM\x00\x01", &[0x80, 0, 0, 0, 0, 0x01]].concat(),
+            Expected { pages: &[((1, 2000), 6952235234307623629), ((1, 10 + 48), 10116879410447762189)], error: Some("write past end of page (row 2000) at offset 19") }),
+        ("cz-8pc4", "truncated graphics", [b"\n\x1bM\x00\x04".as_slice(), &[0xff; 15]].concat(),
+            Expected { pages: &[((2, 96), 2010815047313524573)], error: Some("truncated command at offset 1") }),
+        ("cz-8pc4", "text", b"\nTD-1 \xb1".to_vec(),
+            Expected { pages: &[((6 * 32 - 7, 48 + 35), 10132572147424890742)], error: None }),
+        ("cz-8pc4", "encoded pattern", encoded("cz-8pc4", false),
+            Expected { pages: &[((60, 60), 897513285982022845)], error: None }),
+        ("pc-pr101", "mono band", [b"\x1bJ0003".as_slice(), &[0xff, 0, 0x80], &[0x01, 0x02, 0x04], &[0, 0, 0xc0]].concat(),
+            Expected { pages: &[((3, 24), 4111776017592556139)], error: None }),
+        ("pc-pr101", "colour passes", [b"\x1bC6\x1bJ0001".as_slice(), &[0x0f, 0, 0], b"\r\x1bC3\x1bJ0002", &[0xf0, 0x0f, 0, 0xf0, 0x0f, 0], b"\r\x1bC5\x1bS0001\x81\n\x1bC0\x1bU0004\x00\x00\x80"].concat(),
+            Expected { pages: &[((4, 48), 14817025469058529793)], error: None }),
+        ("pc-pr101", "line feed pitch", [b"\x1bT05\n\n\x1bJ0001".as_slice(), &[0x01, 0, 0x80], b"\x1bT01\n\x1bJ0001", &[0x01, 0, 0x80]].concat(),
+            Expected { pages: &[((1, 15 + 1 + 24), 2754610989867825981)], error: None }),
+        ("pc-pr101", "clipped at the right edge", [b"\x1bL090\x1bF0050\x1bU0200".as_slice(), &[0xff; 3]].concat(),
+            Expected { pages: &[((1494, 24), 1555747926915568941)], error: None }),
+        ("pc-pr101", "band across the bottom edge", [b"\x1bT99".as_slice(), &[b'\n'; 6], b"\x1bT70\n\x1bJ0001", &[0xff; 3], b"\x1bT16\n\x1bJ0001", &[0x01, 0, 0x80]].concat(),
+            Expected { pages: &[((1, 1000), 7592657588940990461), ((1, 20 + 24), 14888651068417007573)], error: Some("write past end of page (row 1000) at offset 21") }),
+        ("pc-pr101", "truncated graphics", [b"\n\x1bJ0004".as_slice(), &[0xff; 8]].concat(),
+            Expected { pages: &[((2, 48), 7953440461205681757)], error: Some("truncated command at offset 1") }),
+        ("pc-pr101", "text and kanji without a font", b"\nTD-1\x1bK0!\x1bH!".to_vec(),
+            Expected { pages: &[((4 * 16 + 32 + 9, 24 + 23), 7293312483409311143)], error: None }),
+        ("pc-pr101", "encoded pattern", encoded("pc-pr101", false),
+            Expected { pages: &[((60, 60), 17302626003057181389)], error: None }),
+        ("cz-6pv1", "full frame", cz6pv1_frame(3 * 992),
+            Expected { pages: &[((512, 992), 10802375723451053978)], error: None }),
+        ("cz-6pv1", "cut off frame", [cz6pv1_frame(992 + 10), cz6pv1_frame(1)].concat(),
+            Expected { pages: &[((512, 992), 17575423748933000926), ((512, 1), 11412180112364556180)], error: Some("truncated command at offset 513025") }),
+        ("cz-6pv1", "truncated frame", [cz6pv1_frame(2), vec![0x80; 100]].concat(),
+            Expected { pages: &[((512, 2), 4928752053540371214)], error: Some("truncated command at offset 0") }),
+        ("cz-6pv1", "encoded pattern", encoded("cz-6pv1", true),
+            Expected { pages: &[((512, 992), 14850475495714464282)], error: None }),
+    ]
+}
+
+#[test]
+fn pages_match_the_golden_hashes() {
+    let mut failures = Vec::new();
+    for (model, name, data, expected) in cases() {
+        let mut printer = (find_model(model).unwrap().new)(&PrinterOptions::default());
+        let (pages, result) = printer.decode(&mut &data[..]);
+        let actual: Vec<_> = pages.iter().map(|page: &Page| (page.covered, hash(&page.canvas))).collect();
+        let error = result.err().map(|e| e.to_string());
+        if actual != expected.pages || error.as_deref() != expected.error {
+            failures.push(format!("{model} {name}: pages {actual:?}, error {error:?}"));
+        }
+    }
+    assert!(failures.is_empty(), "golden mismatches:\n{}", failures.join("\n"));
+}
@@ -0,0 +1,259 @@
+use image::{Rgb, RgbImage};
+
+use super::{Canvas, PLANE_NAMES};
+
+/// How the inks look on paper, used to composite the planes of a canvas.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Inks {
+    pub paper: [u8; 3],
+    /// Colour of one layer of each ink on the paper, indexed by colour.
+    pub colors: [[u8; 3]; 4],
+    /// How far a dot spreads past its cell, as a share of the dot pitch,
+    /// from 0 to 1.
+    pub dot_gain: f32,
+    /// Renders dots as discs instead of filling their cell. Needs a scale
+    /// above 1 to be seen.
+    pub round_dots: bool,
+}
+
+/// A named set of inks.
+#[derive(Debug)]
+pub struct InkPreset {
+    /// Name used on the command line.
+    pub id: &'static str,
+    pub description: &'static str,
+    pub inks: Inks,
+}
+
+/// All ink presets, the first one is the default.
+pub const INK_PRESETS: &[InkPreset] = &[
+    InkPreset {
+        id: "ideal",
+        description: "pure process colours on white paper, square dots",
+        inks: Inks {
+            paper: [255, 255, 255],
+            colors: [[0, 0, 0], [255, 255, 0], [255, 0, 255], [0, 255, 255]],
+            dot_gain: 0.0,
+            round_dots: false,
+        },
+    },
+    InkPreset {
+        id: "fresh",
+        description: "new ribbon on office paper, round dots that show from --dot-scale 4",
+        inks: Inks {
+            paper: [250, 248, 242],
+            colors: [[38, 34, 36], [252, 218, 24], [222, 36, 122], [24, 150, 214]],
+            dot_gain: 0.3,
+            round_dots: true,
+        },
+    },
+    InkPreset {
+        id: "faded",
+        description: "worn ribbon on yellowed paper, round dots that show from --dot-scale 4",
+        inks: Inks {
+            paper: [244, 238, 222],
+            colors: [[92, 88, 94], [240, 222, 122], [206, 118, 158], [112, 168, 204]],
+            dot_gain: 0.15,
+            round_dots: true,
+        },
+    },
+];
+
+/// Looks up an ink preset by its id.
+pub fn find_ink_preset(id: &str) -> Option<&'static InkPreset> {
+    INK_PRESETS.iter().find(|preset| preset.id == id)
+}
+
+/// Parses an ink colour override such as `M=de247a`, returns the colour or
+/// `None` for `paper=...`.
+pub fn parse_ink(arg: &str) -> Result<(Option<u32>, [u8; 3]), String> {
+    let (name, hex) = arg.split_once('=').ok_or_else(|| format!("expected NAME=RRGGBB, got {arg:?}"))?;
+    let color = match PLANE_NAMES.iter().position(|plane| plane.eq_ignore_ascii_case(name)) {
+        Some(color) => Some(color as u32),
+        None if name.eq_ignore_ascii_case("paper") => None,
+        None => return Err(format!("unknown ink {name:?}, expected one of K, Y, M, C or paper")),
+    };
+    let hex = hex.trim_start_matches('#');
+    let value = u32::from_str_radix(hex, 16).ok().filter(|_| hex.len() == 6).ok_or_else(|| format!("bad colour {hex:?}, expected RRGGBB"))?;
+    Ok((color, [(value >> 16) as u8, (value >> 8) as u8, value as u8]))
+}
+
+/// Parses a dot gain override, a share of the dot pitch from 0 to 1.
+pub fn parse_dot_gain(arg: &str) -> Result<f32, String> {
+    let gain: f32 = arg.parse().map_err(|_| format!("expected a number, got {arg:?}"))?;
+    if !(0.0..=1.0).contains(&gain) {
+        return Err(format!("{gain} is not in 0..=1"));
+    }
+    Ok(gain)
+}
+
+impl Default for Inks {
+    fn default() -> Inks {
+        INK_PRESETS[0].inks.clone()
+    }
+}
+
+impl Inks {
+    /// Samples per output pixel in each direction when working out how much
+    /// of it a dot covers.
+    const SAMPLES: u32 = 4;
+
+    /// For every pixel of a dot cell at `scale` pixels per dot, the share of
+    /// it covered by the dot itself and by each of its neighbours, as
+    /// (x offset, y offset, coverage).
+    fn kernel(&self, scale: u32) -> Vec<Vec<(i64, i64, f32)>> {
+        let radius = 0.5 + self.dot_gain.clamp(0.0, 1.0) / 2.0;
+        let sample_area = 1.0 / (Inks::SAMPLES * Inks::SAMPLES) as f32;
+        let mut kernel = Vec::new();
+        for sub_y in 0..scale {
+            for sub_x in 0..scale {
+                let mut weights = Vec::new();
+                for (dx, dy) in (-1..=1).flat_map(|dy| (-1..=1).map(move |dx| (dx, dy))) {
+                    let mut coverage = 0.0;
+                    for (sample_x, sample_y) in (0..Inks::SAMPLES).flat_map(|y| (0..Inks::SAMPLES).map(move |x| (x, y))) {
+                        // position in dot pitches from the centre of the neighbour
+                        let u = (sub_x as f32 + (sample_x as f32 + 0.5) / Inks::SAMPLES as f32) / scale as f32 - 0.5 - dx as f32;
+                        let v = (sub_y as f32 + (sample_y as f32 + 0.5) / Inks::SAMPLES as f32) / scale as f32 - 0.5 - dy as f32;
+                        let inside = if self.round_dots { u.hypot(v) <= radius } else { u.abs().max(v.abs()) <= radius };
+                        if inside {
+                            coverage += sample_area;
+                        }
+                    }
+                    if coverage > 0.0 {
+                        weights.push((dx, dy, coverage));
+                    }
+                }
+                kernel.push(weights);
+            }
+        }
+        kernel
+    }
+
+    /// Composites the planes of a canvas to RGB at `scale` pixels per dot.
+    /// Each layer of ink lets through a share of the light, so inks mix
+    /// where they overprint and get darker where dots of one ink overlap.
+    /// Partial ink covers part of the paper.
+    ///
+    /// Fails if the scaled image would be too large to address.
+    pub fn composite(&self, canvas: &Canvas, scale: u32) -> Result<RgbImage, String> {
+        let scale = scale.max(1);
+        let too_large = || format!("{}x{} canvas too large to composite at {scale} pixels per dot", canvas.width(), canvas.height());
+        let (Some(scaled_width), Some(scaled_height)) = (canvas.width().checked_mul(scale), canvas.height().checked_mul(scale)) else {
+            return Err(too_large());
+        };
+        // three bytes per pixel
+        (scaled_width as usize).checked_mul(scaled_height as usize).and_then(|pixels| pixels.checked_mul(3)).ok_or_else(too_large)?;
+        let kernel = self.kernel(scale);
+        let transmittance = self.colors.map(|color| {
+            std::array::from_fn::<f32, 3, _>(|i| (color[i] as f32 / self.paper[i].max(1) as f32).min(1.0))
+        });
+        let (width, height) = (canvas.width(), canvas.height());
+        if kernel == [vec![(0, 0, 1.0)]] {
+            // every dot fills its pixel, look the light up by the amount of ink
+            let light: Vec<[[f32; 3]; 256]> = transmittance.iter()
+                .map(|t| std::array::from_fn(|ink| t.map(|t| absorb(t, ink as f32 / 255.0))))
+                .collect();
+            let planes: Vec<&[u8]> = (0..4).map(|color| canvas.plane_data(color)).collect();
+            let pixels = (0..width as usize * height as usize).flat_map(|i| {
+                let mut pixel = self.paper.map(|channel| channel as f32);
+                for (plane, light) in planes.iter().zip(&light) {
+                    if plane[i] != 0 {
+                        pixel.iter_mut().zip(light[plane[i] as usize]).for_each(|(channel, factor)| *channel *= factor);
+                    }
+                }
+                pixel.map(|channel| channel.round() as u8)
+            }).collect();
+            return Ok(RgbImage::from_raw(width, height, pixels).unwrap());
+        }
+        Ok(RgbImage::from_fn(scaled_width, scaled_height, |pixel_x, pixel_y| {
+            let (x, y) = ((pixel_x / scale) as i64, (pixel_y / scale) as i64);
+            let weights = &kernel[((pixel_y % scale) * scale + pixel_x % scale) as usize];
+            let mut light = self.paper.map(|channel| channel as f32);
+            for (color, transmittance) in transmittance.iter().enumerate() {
+                let density: f32 = weights.iter()
+                    .filter(|&&(dx, dy, _)| (0..width as i64).contains(&(x + dx)) && (0..height as i64).contains(&(y + dy)))
+                    .map(|&(dx, dy, coverage)| canvas.ink(color as u32, (x + dx) as u32, (y + dy) as u32) as f32 / 255.0 * coverage)
+                    .sum();
+                if density == 0.0 {
+                    continue;
+                }
+                for (channel, &t) in light.iter_mut().zip(transmittance) {
+                    *channel *= absorb(t, density);
+                }
+            }
+            Rgb(light.map(|channel| channel.round() as u8))
+        }))
+    }
+}
+
+/// Share of the light let through by `density` layers of an ink with
+/// transmittance `t`. Whole layers multiply, the rest covers part of the
+/// paper.
+fn absorb(t: f32, density: f32) -> f32 {
+    t.powf(density.floor()) * (1.0 - density.fract() * (1.0 - t))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inks_multiply_where_they_overprint() {
+        let inks = Inks { paper: [200, 200, 200], colors: [[0, 0, 0], [200, 200, 100], [200, 100, 200], [100, 200, 200]], ..Inks::default() };
+        let mut canvas = Canvas::new(3, 1);
+        canvas.dot(0, 0, 1);
+        canvas.dot(1, 0, 1);
+        canvas.dot(1, 0, 2);
+        canvas.set(3, 2, 0, 51);
+        let img = inks.composite(&canvas, 1).unwrap();
+        assert_eq!(img.get_pixel(0, 0), &Rgb([200, 200, 100]));
+        assert_eq!(img.get_pixel(1, 0), &Rgb([200, 100, 100]));
+        assert_eq!(img.get_pixel(2, 0), &Rgb([180, 200, 200]));
+    }
+
+    #[test]
+    fn overlapping_dots_get_darker() {
+        let inks = Inks { dot_gain: 1.0, ..find_ink_preset("fresh").unwrap().inks.clone() };
+        let mut canvas = Canvas::new(4, 1);
+        for x in 0..3 {
+            canvas.dot(x, 0, 2);
+        }
+        let img = inks.composite(&canvas, 1).unwrap();
+        // the middle dot gets ink from both sides, the blank one from one
+        assert!(img.get_pixel(1, 0).0[1] < img.get_pixel(0, 0).0[1]);
+        assert!(img.get_pixel(3, 0).0[1] < inks.paper[1]);
+    }
+
+    #[test]
+    fn round_dots_leave_the_corners() {
+        let inks = Inks { round_dots: true, ..Inks::default() };
+        let mut canvas = Canvas::new(1, 1);
+        canvas.dot(0, 0, 0);
+        let img = inks.composite(&canvas, 8).unwrap();
+        assert_eq!(img.dimensions(), (8, 8));
+        assert_eq!(img.get_pixel(0, 0), &Rgb([255, 255, 255]));
+        assert_eq!(img.get_pixel(4, 4), &Rgb([0, 0, 0]));
+    }
+
+    #[test]
+    fn ink_overrides() {
+        assert_eq!(parse_ink("m=#de247a"), Ok((Some(2), [0xde, 0x24, 0x7a])));
+        assert_eq!(parse_ink("paper=fffff0"), Ok((None, [0xff, 0xff, 0xf0])));
+        assert!(parse_ink("X=000000").is_err());
+        assert!(parse_ink("K=000").is_err());
+    }
+
+    #[test]
+    fn dot_gain_overrides() {
+        assert_eq!(parse_dot_gain("0.25"), Ok(0.25));
+        assert_eq!(parse_dot_gain("1"), Ok(1.0));
+        assert!(parse_dot_gain("-0.1").is_err());
+        assert!(parse_dot_gain("1.5").is_err());
+        assert!(parse_dot_gain("NaN").is_err());
+    }
+
+    #[test]
+    fn oversized_scale_is_an_error() {
+        assert!(Inks::default().composite(&Canvas::new(3, 1), u32::MAX / 2).is_err());
+    }
+}
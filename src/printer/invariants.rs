@@ -0,0 +1,106 @@
+//! Properties that hold for any input, however broken. Checked by the fuzz
+//! targets in `fuzz/`, run with `cargo fuzz run decode_cz8pc4` and so on,
+//! and by a quick randomised test.
+
+use std::sync::Mutex;
+
+use super::{Canvas, Model, Page, PrinterOptions};
+
+/// Decodes `data` with a new printer, fed `chunk` bytes at a time. Returns
+/// every page, blank or not, with the canvas left at the end of the job.
+fn decode_in_chunks(model: &Model, data: &[u8], chunk: usize) -> Vec<Page> {
+    let mut printer = (model.new)(&PrinterOptions::default());
+    let img_mutex = Mutex::new(printer.create_image());
+    let mut pages = Vec::new();
+    for data in data.chunks(chunk) {
+        let _ = printer.feed(data, &img_mutex);
+        pages.extend(printer.take_pages());
+    }
+    let _ = printer.finish();
+    pages.extend(printer.take_pages());
+    pages.push(Page { canvas: img_mutex.into_inner().unwrap(), covered: printer.covered(), dpi: printer.dpi() });
+    pages
+}
+
+/// Checks that a page covers no more than its canvas and that no ink lies
+/// outside the covered area, where cropping would lose it.
+fn check_page(page: &Page) {
+    let canvas = &page.canvas;
+    let (covered_x, covered_y) = page.covered;
+    assert!(covered_x <= canvas.width() && covered_y <= canvas.height(), "covered {:?} on a {}x{} canvas", page.covered, canvas.width(), canvas.height());
+    if page.is_blank() {
+        return;
+    }
+    // slices compare with memcmp, much faster than looking at every dot
+    let blank_row = vec![0; canvas.width() as usize];
+    for color in 0..4 {
+        for (y, row) in canvas.plane_data(color).chunks(canvas.width().max(1) as usize).enumerate() {
+            let limit = if (y as u32) < covered_y { covered_x as usize } else { 0 };
+            assert!(row[limit..] == blank_row[limit..], "ink outside the covered {:?} in row {y}", page.covered);
+        }
+    }
+}
+
+fn same_ink(a: &Canvas, b: &Canvas) -> bool {
+    (a.width(), a.height()) == (b.width(), b.height()) && (0..4).all(|color| a.plane_data(color) == b.plane_data(color))
+}
+
+/// Decodes arbitrary bytes, the first of which picks a chunk size, and
+/// panics if decoding panics, a page breaks `check_page` or feeding the job
+/// in chunks gives other pages than feeding it at once. The automatic model
+/// may guess differently from a shorter sample, so only its pages are
+/// checked.
+pub fn check_decode(model: &Model, data: &[u8]) {
+    let Some((&chunk, data)) = data.split_first() else {
+        return;
+    };
+    let chunked = decode_in_chunks(model, data, chunk as usize + 1);
+    chunked.iter().for_each(check_page);
+    if model.encode.is_none() {
+        return;
+    }
+    let whole = decode_in_chunks(model, data, data.len().max(1));
+    assert_eq!(whole.len(), chunked.len(), "number of pages depends on the chunk size");
+    for (a, b) in whole.iter().zip(&chunked) {
+        assert!(a.covered == b.covered && same_ink(&a.canvas, &b.canvas), "page depends on the chunk size");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::printer::MODELS;
+
+    /// Pieces of commands of every model, so that random jobs get past the
+    /// first byte.
+    const FRAGMENTS: &[&[u8]] = &[
+        b"\x1b", b"\x1bM", b"\x1bM\x00\x02", b"\x1b\x19", b"\x1b%", b"\x1b%99", b"\x1b%00", b"\x1bL", b"\x1b#",
+        b"\x1bJ", b"\x1bJ0002", b"\x1bS0001", b"\x1bU9999", b"\x1bV0003", b"\x1bC", b"\x1bC3", b"\x1bF", b"\x1bF9999",
+        b"\x1bL999", b"\x1bT", b"\x1bT99", b"\x1bK", b"\x1bH", b"\x1bc1", b"\x1bD", b"\n", b"\r", b"\x0c", b"\xc0",
+        b"0123", b"9", b"\xff", b"\x00",
+    ];
+
+    #[test]
+    fn random_jobs_keep_the_invariants() {
+        // xorshift, the same jobs on every run
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        for _ in 0..24 {
+            let mut data = vec![next() as u8];
+            for _ in 0..next() % 64 {
+                match next() % 3 {
+                    0 => data.push(next() as u8),
+                    _ => data.extend(FRAGMENTS[next() as usize % FRAGMENTS.len()]),
+                }
+            }
+            for model in MODELS {
+                check_decode(model, &data);
+            }
+        }
+    }
+}
@@ -0,0 +1,544 @@
+use std::fmt;
+use std::io::{ErrorKind, Read};
+use std::sync::Mutex;
+
+mod auto;
+mod calibration;
+mod canvas;
+mod cz6pv1;
+mod cz8pc4;
+mod dither;
+mod font;
+#[cfg(test)]
+mod golden;
+mod ink;
+#[cfg(any(test, fuzzing))]
+pub mod invariants;
+mod pcpr101;
+#[cfg(test)]
+mod properties;
+
+pub use auto::{Auto, Detection};
+pub use calibration::Calibration;
+pub use canvas::{Canvas, PLANE_NAMES};
+pub use cz6pv1::Cz6pv1;
+pub use cz8pc4::{Cz8pc4, PassOrder};
+pub use dither::{separate, Dither};
+pub use font::KanjiFont;
+pub use ink::{find_ink_preset, parse_dot_gain, parse_ink, InkPreset, Inks, INK_PRESETS};
+pub use pcpr101::Pcpr101;
+
+/// Settings shared by all models, applied when a printer is constructed.
+#[derive(Default, Clone)]
+pub struct PrinterOptions {
+    /// Font for PC-PR101 kanji text.
+    pub kanji_font: Option<KanjiFont>,
+    /// Tone curves for CZ-6PV1 gradation data.
+    pub calibration: Option<Calibration>,
+    /// Prints dot matrix jobs as one banner of up to this many inches instead
+    /// of separate pages.
+    pub banner_length: Option<u32>,
+    /// Called when the `auto` model has decided on a model for a job.
+    pub on_detect: Option<fn(&Detection)>,
+    /// Plane order of the CZ-8PC4 colour passes, forcing colour mode.
+    pub pass_order: Option<PassOrder>,
+    /// Reports questionable input that can still be decoded as errors.
+    pub strict: bool,
+}
+
+/// A supported printer model.
+#[derive(Debug)]
+pub struct Model {
+    /// Name used on the command line.
+    pub id: &'static str,
+    pub name: &'static str,
+    pub description: &'static str,
+    /// Native horizontal and vertical resolution in dots per inch.
+    pub dpi: (u32, u32),
+    /// Page width and height in dots.
+    pub page_size: (u32, u32),
+    pub new: fn(&PrinterOptions) -> Box<dyn Printer>,
+    /// Turns a canvas into a print job, one pixel per dot.
+    pub encode: Option<fn(&Canvas) -> Vec<u8>>,
+}
+
+/// All supported models, the first one is the default.
+pub const MODELS: &[Model] = &[
+    Model {
+        id: "cz-8pc4",
+        name: "Sharp CZ-8PC4",
+        description: "X1 / X68000 colour dot matrix printer",
+        dpi: Cz8pc4::DPI,
+        page_size: (Cz8pc4::PAGE_WIDTH, Cz8pc4::PAGE_HEIGHT),
+        new: |options| {
+            let mut printer = Cz8pc4::default();
+            if let Some(inches) = options.banner_length {
+                printer.set_banner(inches.saturating_mul(Cz8pc4::DPI.1));
+            }
+            if let Some(order) = &options.pass_order {
+                printer.set_pass_order(order.clone());
+            }
+            printer.set_strict(options.strict);
+            Box::new(printer)
+        },
+        encode: Some(Cz8pc4::encode),
+    },
+    Model {
+        id: "cz-6pv1",
+        name: "Sharp CZ-6PV1",
+        description: "X68000 colour video printer",
+        dpi: Cz6pv1::DPI,
+        page_size: (Cz6pv1::PAGE_WIDTH, Cz6pv1::PAGE_HEIGHT),
+        new: |options| {
+            let mut printer = Cz6pv1::default();
+            if let Some(calibration) = &options.calibration {
+                printer.set_calibration(calibration);
+            }
+            Box::new(printer)
+        },
+        encode: Some(Cz6pv1::encode),
+    },
+    Model {
+        id: "pc-pr101",
+        name: "NEC PC-PR101",
+        description: "PC-98 colour dot matrix printer",
+        dpi: Pcpr101::DPI,
+        page_size: (Pcpr101::PAGE_WIDTH, Pcpr101::PAGE_HEIGHT),
+        new: |options| {
+            let mut printer = Pcpr101::default();
+            if let Some(font) = &options.kanji_font {
+                printer.set_kanji_font(font.clone());
+            }
+            if let Some(inches) = options.banner_length {
+                printer.set_banner(inches.saturating_mul(Pcpr101::DPI.1));
+            }
+            Box::new(printer)
+        },
+        encode: Some(Pcpr101::encode),
+    },
+    Model {
+        id: "auto",
+        name: "Automatic",
+        description: "detects the model from each print job, falling back to the first one",
+        // the page fits every model, the resolution is only right for the fallback
+        dpi: Cz8pc4::DPI,
+        page_size: (Cz8pc4::PAGE_WIDTH, Cz8pc4::PAGE_HEIGHT),
+        new: |options| Box::new(Auto::new(options.clone())),
+        encode: None,
+    },
+];
+
+/// Looks up a model by its id.
+pub fn find_model(id: &str) -> Option<&'static Model> {
+    MODELS.iter().find(|model| model.id == id)
+}
+
+pub trait Printer {
+    fn create_image(&self) -> Canvas;
+    /// Horizontal and vertical resolution of the image in dots per inch.
+    fn dpi(&self) -> (u32, u32);
+    /// Decodes the next chunk of a print job into the image. Chunks may be split
+    /// anywhere, commands that are not complete yet are kept until more data
+    /// arrives. Decoding continues past errors, only the first one in the chunk
+    /// is returned.
+    fn feed(&mut self, data: &[u8], img_mutex: &Mutex<Canvas>) -> Result<(), DecodeError> {
+        self.feed_traced(data, img_mutex, None)
+    }
+    /// Like `feed`, but also reports every command to `trace` as it is executed.
+    fn feed_traced(&mut self, data: &[u8], img_mutex: &Mutex<Canvas>, trace: Option<&mut dyn FnMut(Trace)>) -> Result<(), DecodeError>;
+    /// Ends the current print job and returns the covered width and height.
+    /// The next call to `feed` starts a new job.
+    fn finish(&mut self) -> Result<(u32, u32), DecodeError>;
+    /// Width and height covered by the current (or just finished) job, in
+    /// dots from the top left corner: a dot printed at (x, y) covers at least
+    /// (x + 1, y + 1), nothing printed is (0, 0).
+    fn covered(&self) -> (u32, u32);
+
+    /// Pages finished by a form feed or by running off the bottom of the
+    /// page since the last call. The page being printed stays in the image.
+    fn take_pages(&mut self) -> Vec<Page> {
+        Vec::new()
+    }
+
+    /// Decodes a whole print job from a reader into pages. Decoding continues
+    /// past errors, the first one is returned along with everything decoded.
+    /// A read error ends the job early and is returned instead, as the pages
+    /// are incomplete. Blank pages are left out.
+    fn decode(&mut self, input: &mut dyn Read) -> (Vec<Page>, Result<(), DecodeError>) {
+        let img_mutex = Mutex::new(self.create_image());
+        let mut pages = Vec::new();
+        let mut buf = [0; 4096];
+        let mut result = Ok(());
+        let mut offset = 0;
+        loop {
+            match input.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => {
+                    result = result.and(self.feed(&buf[..n], &img_mutex));
+                    offset += n;
+                },
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    result = Err(DecodeError::Read { offset, message: e.to_string() });
+                    break;
+                },
+            }
+            pages.extend(self.take_pages());
+        }
+        let finished = self.finish();
+        pages.extend(self.take_pages());
+        let page = Page { canvas: img_mutex.into_inner().unwrap(), covered: self.covered(), dpi: self.dpi() };
+        if !page.is_blank() {
+            pages.push(page);
+        }
+        (pages, result.and(finished.map(|_| ())))
+    }
+}
+
+/// A printed page.
+pub struct Page {
+    pub canvas: Canvas,
+    /// Width and height covered by the print, see `Printer::covered`.
+    pub covered: (u32, u32),
+    /// Horizontal and vertical resolution in dots per inch.
+    pub dpi: (u32, u32),
+}
+
+impl Page {
+    pub fn is_blank(&self) -> bool {
+        self.covered.0 == 0 || self.covered.1 == 0
+    }
+}
+
+/// Moves the printed page out of the image, leaving a blank one of the same
+/// size. Returns `None` if nothing was printed.
+fn eject(img_mutex: &Mutex<Canvas>, covered: (u32, u32), dpi: (u32, u32)) -> Option<Page> {
+    let mut img = img_mutex.lock().unwrap();
+    let blank = img.blank();
+    let page = Page { canvas: std::mem::replace(&mut *img, blank), covered, dpi };
+    (!page.is_blank()).then_some(page)
+}
+
+/// Continuous feed, makes the image at least `height` rows tall by adding
+/// whole pages, but no taller than `max_height`.
+fn grow(img_mutex: &Mutex<Canvas>, height: u32, page_height: u32, max_height: u32) {
+    let mut img = img_mutex.lock().unwrap();
+    if img.height() >= height.min(max_height) {
+        return;
+    }
+    img.set_height(height.div_ceil(page_height).saturating_mul(page_height).min(max_height));
+}
+
+/// Paper feed of the line printers: sheets of `page_height` rows that are
+/// ejected as the head moves past them, or a banner on continuous paper.
+pub(crate) struct Paper {
+    page_height: u32,
+    band_height: u32, // rows printed below the head
+    dpi: (u32, u32),
+    banner_height: Option<u32>, // continuous feed up to this many rows instead of pages
+    pub(crate) covered: (u32, u32), // printed area of the current page
+    pub(crate) pages: Vec<Page>,
+}
+
+impl Paper {
+    pub(crate) fn new(page_height: u32, band_height: u32, dpi: (u32, u32)) -> Paper {
+        Paper { page_height, band_height, dpi, banner_height: None, covered: (0, 0), pages: Vec::new() }
+    }
+
+    /// Prints on continuous paper, the image grows up to `max_height` rows
+    /// instead of being split into pages.
+    pub(crate) fn set_banner(&mut self, max_height: u32) {
+        self.banner_height = Some(max_height);
+    }
+
+    /// Rows the head can reach, on the page or the whole banner.
+    pub(crate) fn height(&self) -> u32 {
+        self.banner_height.unwrap_or(self.page_height)
+    }
+
+    /// Starts a new job, dropping the pages of the last one.
+    pub(crate) fn reset(&mut self) {
+        self.covered = (0, 0);
+        self.pages.clear();
+    }
+
+    /// Ejects the current page, the head keeps its position on the next one.
+    fn new_page(&mut self, img_mutex: &Mutex<Canvas>) {
+        self.pages.extend(eject(img_mutex, self.covered, self.dpi));
+        self.covered = (0, 0);
+    }
+
+    /// Feeds the paper after a line feed moved the head down to `head_y`.
+    /// Returns the row of the head on the page it ends up on.
+    pub(crate) fn line_feed(&mut self, head_y: u32, img_mutex: &Mutex<Canvas>) -> u32 {
+        match self.banner_height {
+            Some(max_height) => {
+                grow(img_mutex, head_y.saturating_add(self.band_height), self.page_height, max_height);
+                head_y
+            },
+            // the line ends up near the top of the next page
+            None if head_y >= self.page_height => {
+                self.new_page(img_mutex);
+                head_y - self.page_height
+            },
+            None => head_y,
+        }
+    }
+
+    /// Feeds the paper to the top of the next page. Returns the new row of
+    /// the head.
+    pub(crate) fn form_feed(&mut self, head_y: u32, img_mutex: &Mutex<Canvas>) -> u32 {
+        match self.banner_height {
+            // on a banner, move on to where the next page would start, also
+            // from the top of a page
+            Some(max_height) => {
+                let head_y = (head_y / self.page_height + 1).saturating_mul(self.page_height);
+                grow(img_mutex, head_y.saturating_add(self.band_height), self.page_height, max_height);
+                head_y
+            },
+            None => {
+                self.new_page(img_mutex);
+                0
+            },
+        }
+    }
+}
+
+/// Errors found while decoding a print job. Offsets are in bytes from the
+/// start of the job and point at the first byte of the offending command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The stream ended in the middle of a command or its data.
+    TruncatedCommand { offset: usize },
+    /// A numeric argument could not be parsed.
+    BadNumericField { offset: usize, field: Vec<u8> },
+    /// A command byte the decoder does not know how to interpret.
+    UnknownCommand { offset: usize, code: u8 },
+    /// The print head moved past the bottom of the page, or dots were printed
+    /// below it. Those dots are lost.
+    OutOfPage { offset: usize, y: u32 },
+    /// A line printed in a different number of colour passes than the pass
+    /// order has, only reported in strict mode.
+    ColorPasses { offset: usize, passes: u32, expected: u32 },
+    /// Reading the job failed after `offset` bytes, the rest of it is missing.
+    Read { offset: usize, message: String },
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::TruncatedCommand { offset } => write!(f, "truncated command at offset {offset}"),
+            DecodeError::BadNumericField { offset, field } => write!(f, "bad numeric field {:?} at offset {offset}", String::from_utf8_lossy(field)),
+            DecodeError::UnknownCommand { offset, code } => write!(f, "unknown command {code:#04x} at offset {offset}"),
+            DecodeError::OutOfPage { offset, y } => write!(f, "write past end of page (row {y}) at offset {offset}"),
+            DecodeError::ColorPasses { offset, passes, expected } => write!(f, "line with {passes} colour passes instead of {expected} ending at offset {offset}"),
+            DecodeError::Read { offset, message } => write!(f, "read error after {offset} bytes: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// One decoded command, as reported by `Printer::feed_traced`.
+pub struct Trace<'a> {
+    pub offset: usize,
+    /// The command bytes, without any graphics data following them.
+    pub raw: &'a [u8],
+    pub description: String,
+}
+
+impl<'a> Trace<'a> {
+    fn new(offset: usize, raw: &'a [u8], mut description: String, result: &Result<(), DecodeError>) -> Trace<'a> {
+        if let Err(e) = result {
+            description = format!("{description} ({e})");
+        }
+        Trace { offset, raw, description }
+    }
+
+    fn error(offset: usize, raw: &'a [u8], e: &DecodeError) -> Trace<'a> {
+        let description = match e {
+            DecodeError::UnknownCommand { code, .. } if raw.first() == Some(&0x1b) => format!("unsupported ESC {code:#04x}"),
+            DecodeError::UnknownCommand { code, .. } => format!("unsupported command {code:#04x}"),
+            _ => format!("error: {e}"),
+        };
+        Trace { offset, raw, description }
+    }
+}
+
+fn color_name(color: u32) -> &'static str {
+    match color {
+        0 => "black",
+        1 => "yellow",
+        2 => "magenta",
+        3 => "cyan",
+        _ => unreachable!(),
+    }
+}
+
+fn describe_data(c: u8) -> String {
+    if c.is_ascii_graphic() || c == b' ' {
+        format!("data {c:#04x} {:?} (ignored)", c as char)
+    } else {
+        format!("data {c:#04x} (ignored)")
+    }
+}
+
+/// Outcome of parsing the front of the pending input.
+pub(crate) enum Parsed<C> {
+    /// A complete command and its length in bytes.
+    Command(usize, C),
+    /// An invalid command, the given number of bytes is skipped.
+    Invalid(usize, DecodeError),
+    /// More input is needed.
+    Incomplete,
+}
+
+/// Model specific command parser. Parsers may keep state between commands,
+/// e.g. the number of graphics columns still to come.
+pub(crate) trait Parse: Default {
+    type Command;
+    fn parse(&mut self, buf: &[u8], offset: usize) -> Parsed<Self::Command>;
+    /// Offset of a command whose data is still being received, if any.
+    fn in_progress(&self) -> Option<usize>;
+}
+
+/// Buffers input between calls to `Printer::feed` and splits it into commands.
+#[derive(Default)]
+pub(crate) struct Tokenizer<P> {
+    parser: P,
+    pending: Vec<u8>,
+    pos: usize,
+    last: usize, // start of the last command in pending
+    offset: usize, // job offset of pending[0]
+    finished: bool, // the job ended, the next push starts a new one
+}
+
+impl<P: Parse> Tokenizer<P> {
+    pub(crate) fn push(&mut self, data: &[u8]) {
+        self.pending.drain(..self.pos);
+        self.offset += self.pos;
+        self.pos = 0;
+        self.last = 0;
+        self.pending.extend_from_slice(data);
+    }
+
+    /// Returns the next complete command with its offset, or `None` if more
+    /// input is needed.
+    pub(crate) fn next(&mut self) -> Option<Result<(usize, P::Command), DecodeError>> {
+        let offset = self.offset + self.pos;
+        self.last = self.pos;
+        match self.parser.parse(&self.pending[self.pos..], offset) {
+            Parsed::Command(len, command) => {
+                self.pos += len;
+                Some(Ok((offset, command)))
+            },
+            Parsed::Invalid(len, e) => {
+                self.pos += len;
+                Some(Err(e))
+            },
+            Parsed::Incomplete => None,
+        }
+    }
+
+    /// Offset and bytes of the command last returned by `next`.
+    pub(crate) fn last(&self) -> (usize, &[u8]) {
+        (self.offset + self.last, &self.pending[self.last..self.pos])
+    }
+
+    /// Ends the job, checking that the stream did not end in the middle of a
+    /// command.
+    pub(crate) fn finish(&mut self) -> Result<(), DecodeError> {
+        self.finished = true;
+        match self.parser.in_progress() {
+            Some(offset) => Err(DecodeError::TruncatedCommand { offset }),
+            None if self.pos < self.pending.len() => Err(DecodeError::TruncatedCommand { offset: self.offset + self.pos }),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Command loop shared by the decoders, the model specific part of a `Printer`.
+pub(crate) trait Decode: Printer {
+    type Parser: Parse;
+    fn tokens(&mut self) -> &mut Tokenizer<Self::Parser>;
+    /// Starts a new job.
+    fn reset(&mut self);
+    fn execute(&mut self, offset: usize, command: &<Self::Parser as Parse>::Command, img_mutex: &Mutex<Canvas>) -> Result<(), DecodeError>;
+    /// Describes a command for the disassembler, using the state after it ran.
+    fn describe(&self, command: &<Self::Parser as Parse>::Command) -> Option<String>;
+}
+
+/// `Printer::feed_traced` for a `Decode` model.
+fn feed_commands<D: Decode>(decoder: &mut D, data: &[u8], img_mutex: &Mutex<Canvas>, mut trace: Option<&mut dyn FnMut(Trace)>) -> Result<(), DecodeError> {
+    if decoder.tokens().finished {
+        decoder.reset();
+    }
+    decoder.tokens().push(data);
+    let mut result = Ok(());
+    while let Some(token) = decoder.tokens().next() {
+        let executed = match token {
+            Ok((offset, command)) => {
+                let executed = decoder.execute(offset, &command, img_mutex);
+                if let Some(trace) = &mut trace && let Some(description) = decoder.describe(&command) {
+                    trace(Trace::new(offset, decoder.tokens().last().1, description, &executed));
+                }
+                executed
+            },
+            Err(e) => {
+                if let Some(trace) = &mut trace {
+                    let (offset, raw) = decoder.tokens().last();
+                    trace(Trace::error(offset, raw, &e));
+                }
+                Err(e)
+            },
+        };
+        result = result.and(executed);
+    }
+    result
+}
+
+/// `Printer::finish` for a `Decode` model.
+fn finish_commands<D: Decode>(decoder: &mut D) -> Result<(u32, u32), DecodeError> {
+    decoder.tokens().finish()?;
+    Ok(decoder.covered())
+}
+
+fn parse_decimal(field: &[u8], offset: usize) -> Result<u32, DecodeError> {
+    Some(field).filter(|f| f.iter().all(u8::is_ascii_digit))
+        .and_then(|f| std::str::from_utf8(f).ok())
+        .and_then(|s| s.parse::<u32>().ok())
+        .ok_or_else(|| DecodeError::BadNumericField { offset, field: field.to_vec() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn models_match_their_printers() {
+        for model in MODELS {
+            assert_eq!(find_model(model.id).map(|m| m.name), Some(model.name));
+            let img = (model.new)(&PrinterOptions::default()).create_image();
+            assert_eq!((img.width(), img.height()), model.page_size, "{}", model.id);
+        }
+        assert!(find_model("pc-pr201").is_none());
+    }
+
+    /// A reader that fails, like a serial port that was unplugged.
+    struct Unplugged;
+
+    impl Read for Unplugged {
+        fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+            Err(std::io::Error::other("unplugged"))
+        }
+    }
+
+    #[test]
+    fn read_errors_end_the_job() {
+        let data = b"\x1bM\x00\x01\x80\x00\x00\x00\x00\x00";
+        let (pages, result) = Cz8pc4::default().decode(&mut data.chain(Unplugged));
+        assert_eq!(result, Err(DecodeError::Read { offset: 10, message: "unplugged".to_string() }));
+        assert_eq!(pages.len(), 1);
+        assert_eq!(pages[0].canvas.ink(0, 0, 0), 255);
+    }
+}
@@ -0,0 +1,649 @@
+use std::sync::Mutex;
+
+use super::font::{self, KanjiFont};
+use super::{color_name, describe_data, feed_commands, finish_commands, parse_decimal, Canvas, Decode, DecodeError, Page, Paper, Parse, Parsed, Printer, Tokenizer, Trace};
+
+/// A single command in a PC-PR101 byte stream.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// ESC C n, select color
+    SelectColor(u8),
+    /// ESC D, copy mode
+    CopyMode,
+    /// ESC F nnnn, dot addressing, in dots from the left margin
+    DotAddress(u32),
+    /// ESC L nnn, left margin, in ANK columns
+    LeftMargin(u32),
+    /// ESC T nn, line feed pitch in 1/120 inch
+    LineFeedPitch(u32),
+    /// ESC S / ESC I / ESC J nnnn, 8, 16 or 24 dot graphics followed by the
+    /// given number of columns
+    Graphics { dots: u32, columns: u32 },
+    /// ESC V / ESC W / ESC U nnnn, 8, 16 or 24 dot graphics repeating a single
+    /// column the given number of times
+    Repeat { dots: u32, columns: u32, pins: u32 },
+    /// One column of graphics data, as a mask of the 24 head pins with the top
+    /// one in bit 0
+    Column(u32),
+    /// ESC c n, soft reset
+    SoftReset(u8),
+    /// ESC K, kanji in
+    KanjiIn,
+    /// ESC H, kanji out, back to ANK text
+    KanjiOut,
+    /// A 2 byte JIS X 0208 character in kanji mode
+    Kanji(u16),
+    LineFeed,
+    /// Form feed, starts a new page
+    FormFeed,
+    CarriageReturn,
+    /// Any other byte, printable ones are printed as ANK text
+    Data(u8),
+}
+
+#[derive(Default)]
+pub struct Parser {
+    columns_left: u32,
+    column_bytes: usize,
+    start: usize,
+    kanji: bool,
+}
+
+/// Graphics mode for an escape code: dots per column and the repeat flag.
+fn graphics_mode(b: u8) -> Option<(u32, bool)> {
+    match b {
+        0x53 => Some((8, false)), // ESC S
+        0x49 => Some((16, false)), // ESC I
+        0x4a => Some((24, false)), // ESC J
+        0x56 => Some((8, true)), // ESC V
+        0x57 => Some((16, true)), // ESC W
+        0x55 => Some((24, true)), // ESC U
+        _ => None,
+    }
+}
+
+/// Maps graphics data bytes onto the 24 head pins. All modes cover 16/120
+/// inch, so 8 dot graphics use 3 pins per dot and 16 dot graphics 1.5.
+fn column_pins(data: &[u8]) -> u32 {
+    let bits = data.iter().rev().fold(0u32, |bits, &b| bits << 8 | b as u32);
+    let dots = data.len() as u32 * 8;
+    let mut pins = 0;
+    for i in (0..dots).filter(|i| bits >> i & 1 != 0) {
+        let first = i * 24 / dots;
+        let last = ((i + 1) * 24 - 1) / dots;
+        for pin in first..=last {
+            pins |= 1 << pin;
+        }
+    }
+    pins
+}
+
+impl Parse for Parser {
+    type Command = Command;
+
+    fn parse(&mut self, buf: &[u8], offset: usize) -> Parsed<Command> {
+        if self.columns_left > 0 {
+            let Some(p) = buf.get(..self.column_bytes) else {
+                return Parsed::Incomplete;
+            };
+            self.columns_left -= 1;
+            return Parsed::Command(self.column_bytes, Command::Column(column_pins(p)));
+        }
+        let Some(&c) = buf.first() else {
+            return Parsed::Incomplete;
+        };
+        match c {
+            // escape
+            0x1b => {
+                let Some(&b) = buf.get(1) else {
+                    return Parsed::Incomplete;
+                };
+                let len = match (b, graphics_mode(b)) {
+                    (_, Some((dots, true))) => 6 + dots as usize / 8,
+                    (_, Some((_, false))) => 6,
+                    (0x43, _) => 3,
+                    (0x46, _) => 6,
+                    (0x4c, _) => 5,
+                    (0x54, _) => 4,
+                    (0x63, _) => 3,
+                    _ => 2,
+                };
+                if buf.len() < len {
+                    return Parsed::Incomplete;
+                }
+                if let Some((dots, repeat)) = graphics_mode(b) {
+                    let columns = match parse_decimal(&buf[2..6], offset) {
+                        Ok(columns) => columns,
+                        Err(e) => return Parsed::Invalid(len, e),
+                    };
+                    let command = if repeat {
+                        Command::Repeat { dots, columns, pins: column_pins(&buf[6..len]) }
+                    } else {
+                        self.columns_left = columns;
+                        self.column_bytes = dots as usize / 8;
+                        self.start = offset;
+                        Command::Graphics { dots, columns }
+                    };
+                    return Parsed::Command(len, command);
+                }
+                let command = match b {
+                    0x43 => Command::SelectColor(buf[2]),
+                    0x44 => Command::CopyMode,
+                    0x46 => match parse_decimal(&buf[2..6], offset) {
+                        Ok(x) => Command::DotAddress(x),
+                        Err(e) => return Parsed::Invalid(len, e),
+                    },
+                    0x4c => match parse_decimal(&buf[2..5], offset) {
+                        Ok(column) => Command::LeftMargin(column),
+                        Err(e) => return Parsed::Invalid(len, e),
+                    },
+                    0x54 => match parse_decimal(&buf[2..4], offset) {
+                        Ok(pitch) => Command::LineFeedPitch(pitch),
+                        Err(e) => return Parsed::Invalid(len, e),
+                    },
+                    0x63 => Command::SoftReset(buf[2]),
+                    0x4b => {
+                        self.kanji = true;
+                        Command::KanjiIn
+                    },
+                    0x48 => {
+                        self.kanji = false;
+                        Command::KanjiOut
+                    },
+                    _ => {
+                        return Parsed::Invalid(2, DecodeError::UnknownCommand { offset, code: b });
+                    },
+                };
+                Parsed::Command(len, command)
+            },
+            0x0a => Parsed::Command(1, Command::LineFeed),
+            0x0c => Parsed::Command(1, Command::FormFeed),
+            0x0d => Parsed::Command(1, Command::CarriageReturn),
+            0x21..=0x7e if self.kanji => {
+                let Some(&c2) = buf.get(1) else {
+                    return Parsed::Incomplete;
+                };
+                Parsed::Command(2, Command::Kanji(u16::from_be_bytes([c, c2])))
+            },
+            _ => Parsed::Command(1, Command::Data(c)),
+        }
+    }
+
+    fn in_progress(&self) -> Option<usize> {
+        (self.columns_left > 0).then_some(self.start)
+    }
+}
+
+pub struct Pcpr101 {
+    tokens: Tokenizer<Parser>,
+    head_x: u32,
+    head_y: u32,
+    head_y_half: u32, // pitches in 1/120 inch can end half way between dots
+    color: u32, // 0 = black, 1 = yellow, 2 = magenta, 3 = cyan
+    left_margin: u32, // in dots
+    line_feed_pitch: u32, // in 1/120 inch
+    kanji_font: Option<KanjiFont>,
+    paper: Paper,
+}
+
+impl Default for Pcpr101 {
+    fn default() -> Pcpr101 {
+        Pcpr101 {
+            tokens: Tokenizer::default(),
+            head_x: 0,
+            head_y: 0,
+            head_y_half: 0,
+            color: 0,
+            left_margin: 0,
+            line_feed_pitch: Pcpr101::DEFAULT_LINE_FEED_PITCH,
+            kanji_font: None,
+            paper: Paper::new(Pcpr101::PAGE_HEIGHT, Pcpr101::BAND_HEIGHT, Pcpr101::DPI),
+        }
+    }
+}
+
+impl Pcpr101 {
+    pub const PAGE_WIDTH: u32 = 1494;
+    pub const PAGE_HEIGHT: u32 = 1000;
+    pub const DPI: (u32, u32) = (160, 180);
+    /// Height of a 24 dot graphics band or a kanji, the most printed in one pass.
+    const BAND_HEIGHT: u32 = 24;
+    /// One 24 dot graphics band, 16/120 inch.
+    const DEFAULT_LINE_FEED_PITCH: u32 = 16;
+    /// ANK text is printed at 10 characters per inch, the horizontal resolution is 160 dpi.
+    const CHAR_PITCH: u32 = 16;
+    /// Enlargement of the character ROM glyphs, giving 15x21 dot characters.
+    const CHAR_SCALE: u32 = 3;
+    /// Kanji take two ANK cells so mixed text stays aligned.
+    const KANJI_PITCH: u32 = 2 * Pcpr101::CHAR_PITCH;
+
+    /// Sets the font used for kanji. Without one, kanji print as empty boxes.
+    pub fn set_kanji_font(&mut self, font: KanjiFont) {
+        self.kanji_font = Some(font);
+    }
+
+    /// Encodes a canvas as a job of 24 dot graphics bands, one pixel per
+    /// dot, with a pass for each colour used on a band. Pages are split at
+    /// the page height.
+    pub fn encode(canvas: &Canvas) -> Vec<u8> {
+        // colour select codes, indexed by colour
+        const COLOR_CODES: [u8; 4] = [b'0', b'6', b'3', b'5'];
+        let (width, height) = (canvas.width().min(Pcpr101::PAGE_WIDTH), canvas.height());
+        let mut data = Vec::new();
+        for page_y in (0..height).step_by(Pcpr101::PAGE_HEIGHT as usize) {
+            if page_y > 0 {
+                data.push(0x0c);
+            }
+            let page_end = (page_y + Pcpr101::PAGE_HEIGHT).min(height);
+            for band_y in (page_y..page_end).step_by(Pcpr101::BAND_HEIGHT as usize) {
+                let rows = band_y..(band_y + Pcpr101::BAND_HEIGHT).min(page_end);
+                let mut passes = 0;
+                for (color, code) in COLOR_CODES.into_iter().enumerate() {
+                    let color = color as u32;
+                    let Some(last) = (0..width).rev().find(|&x| rows.clone().any(|y| canvas.ink(color, x, y) != 0)) else {
+                        continue;
+                    };
+                    if passes > 0 {
+                        data.push(b'\r');
+                    }
+                    passes += 1;
+                    data.extend(format!("\x1bC{}\x1bJ{:04}", code as char, last + 1).bytes());
+                    for x in 0..=last {
+                        let pins = rows.clone().filter(|&y| canvas.ink(color, x, y) != 0).fold(0u32, |pins, y| pins | 1 << (y - band_y));
+                        data.extend(&pins.to_le_bytes()[..3]);
+                    }
+                }
+                data.push(b'\n');
+            }
+        }
+        data
+    }
+
+    /// Prints on continuous paper, the image grows up to `max_height` rows
+    /// instead of being split into pages.
+    pub fn set_banner(&mut self, max_height: u32) {
+        self.paper.set_banner(max_height);
+    }
+
+    /// A printer with the head at `(x, y)`, to test the far edges.
+    #[cfg(test)]
+    pub(crate) fn with_head(x: u32, y: u32) -> Pcpr101 {
+        Pcpr101 { head_x: x, head_y: y, ..Pcpr101::default() }
+    }
+}
+
+impl Decode for Pcpr101 {
+    type Parser = Parser;
+
+    fn tokens(&mut self) -> &mut Tokenizer<Parser> {
+        &mut self.tokens
+    }
+
+    fn reset(&mut self) {
+        self.tokens = Tokenizer::default();
+        self.head_x = 0;
+        self.head_y = 0;
+        self.head_y_half = 0;
+        self.color = 0;
+        self.left_margin = 0;
+        self.line_feed_pitch = Pcpr101::DEFAULT_LINE_FEED_PITCH;
+        self.paper.reset();
+    }
+
+    fn execute(&mut self, offset: usize, command: &Command, img_mutex: &Mutex<Canvas>) -> Result<(), DecodeError> {
+        match command {
+            Command::SelectColor(color) => {
+                self.color = match *color {
+                    b'0' => 0,
+                    b'3' => 2,
+                    b'5' => 3,
+                    b'6' => 1,
+                    _ => {
+                        self.color = 0;
+                        return Err(DecodeError::BadNumericField { offset, field: vec![*color] });
+                    },
+                };
+            },
+            Command::DotAddress(x) => {
+                self.head_x = self.left_margin + x;
+            },
+            Command::LeftMargin(column) => {
+                // a head that has not moved on this line moves along with the margin
+                if self.head_x == self.left_margin {
+                    self.head_x = column * Pcpr101::CHAR_PITCH;
+                }
+                self.left_margin = column * Pcpr101::CHAR_PITCH;
+            },
+            Command::LineFeedPitch(pitch) => {
+                self.line_feed_pitch = *pitch;
+            },
+            Command::CopyMode | Command::SoftReset(_) | Command::KanjiIn | Command::KanjiOut => {},
+            Command::Graphics { .. } => {
+                if self.head_y >= self.paper.height() {
+                    return Err(DecodeError::OutOfPage { offset, y: self.head_y });
+                }
+            },
+            Command::Repeat { columns, pins, .. } => {
+                if self.head_y >= self.paper.height() {
+                    return Err(DecodeError::OutOfPage { offset, y: self.head_y });
+                }
+                // columns past the right edge only move the head
+                let on_page = (*columns).min(Pcpr101::PAGE_WIDTH.saturating_sub(self.head_x));
+                let mut drawn = Ok(());
+                for _ in 0..on_page {
+                    drawn = drawn.and(self.draw_column(offset, *pins, img_mutex));
+                    self.head_x += 1;
+                }
+                self.head_x = self.head_x.saturating_add(columns - on_page);
+                self.paper.covered.0 = self.paper.covered.0.max(self.head_x.min(Pcpr101::PAGE_WIDTH));
+                return drawn;
+            },
+            Command::Column(pins) => {
+                let drawn = self.draw_column(offset, *pins, img_mutex);
+                self.head_x = self.head_x.saturating_add(1);
+                self.paper.covered.0 = self.paper.covered.0.max(self.head_x.min(Pcpr101::PAGE_WIDTH));
+                return drawn;
+            },
+            Command::LineFeed => {
+                // the vertical resolution is 180 dpi, 3 half dots per 1/120 inch
+                let half_dots = self.head_y_half + self.line_feed_pitch * 3;
+                self.head_x = self.left_margin;
+                self.head_y = self.head_y.saturating_add(half_dots / 2);
+                self.head_y_half = half_dots % 2;
+                self.head_y = self.paper.line_feed(self.head_y, img_mutex);
+            },
+            Command::FormFeed => {
+                self.head_x = self.left_margin;
+                self.head_y_half = 0;
+                self.head_y = self.paper.form_feed(self.head_y, img_mutex);
+            },
+            Command::CarriageReturn => {
+                self.head_x = self.left_margin;
+            },
+            Command::Kanji(code) => {
+                let glyph = self.kanji_font.as_ref().and_then(|font| font.glyph(*code)).unwrap_or(&font::MISSING_KANJI);
+                let dots: Vec<_> = font::kanji_dots(glyph).collect();
+                let drawn = self.draw_dots(offset, dots, img_mutex);
+                self.head_x = self.head_x.saturating_add(Pcpr101::KANJI_PITCH);
+                return drawn;
+            },
+            Command::Data(c) => {
+                if let Some(glyph) = font::glyph(*c) {
+                    let drawn = self.draw_dots(offset, font::dots(glyph, Pcpr101::CHAR_SCALE, Pcpr101::CHAR_SCALE), img_mutex);
+                    self.head_x = self.head_x.saturating_add(Pcpr101::CHAR_PITCH);
+                    return drawn;
+                }
+            },
+        }
+        Ok(())
+    }
+
+    fn describe(&self, command: &Command) -> Option<String> {
+        Some(match command {
+            Command::SelectColor(_) => format!("ESC C select colour {}", color_name(self.color)),
+            Command::CopyMode => "ESC D copy mode".to_string(),
+            Command::DotAddress(x) => format!("ESC F dot address {x}, column {}", self.head_x),
+            Command::LeftMargin(column) => format!("ESC L left margin {column}, column {}", self.left_margin),
+            Command::LineFeedPitch(pitch) => format!("ESC T line feed pitch {pitch}/120 inch"),
+            Command::Graphics { dots, columns } => {
+                let code = match dots { 8 => 'S', 16 => 'I', _ => 'J' };
+                format!("ESC {code} {dots}-dot graphics, {columns} columns")
+            },
+            Command::Repeat { dots, columns, .. } => {
+                let code = match dots { 8 => 'V', 16 => 'W', _ => 'U' };
+                format!("ESC {code} {dots}-dot repeat, {columns} columns")
+            },
+            Command::Column(_) => return None,
+            Command::SoftReset(_) => "ESC c soft reset".to_string(),
+            Command::KanjiIn => "ESC K kanji mode".to_string(),
+            Command::KanjiOut => "ESC H ANK mode".to_string(),
+            Command::Kanji(code) => match self.kanji_font.as_ref().and_then(|font| font.glyph(*code)) {
+                Some(_) => format!("kanji {code:#06x}"),
+                None => format!("kanji {code:#06x} (no glyph)"),
+            },
+            Command::LineFeed => format!("LF +{}/120 inch, row {}", self.line_feed_pitch, self.head_y),
+            Command::FormFeed => "FF new page".to_string(),
+            Command::CarriageReturn => "CR".to_string(),
+            Command::Data(c) if font::glyph(*c).is_some() => font::describe(*c),
+            Command::Data(c) => describe_data(*c),
+        })
+    }
+}
+
+impl Pcpr101 {
+    /// Draws a graphics column at the head. Dots past the right edge are
+    /// clipped, inked dots below the bottom are reported as out of the page.
+    fn draw_column(&mut self, offset: usize, pins: u32, img_mutex: &Mutex<Canvas>) -> Result<(), DecodeError> {
+        let mut img = img_mutex.lock().unwrap();
+        let page_width = img.width();
+        let page_height = img.height();
+        let pixel_x = self.head_x;
+        if pixel_x >= page_width {
+            return Ok(());
+        }
+        let mut result = Ok(());
+        for y in 0..24 {
+            let pixel_y = self.head_y.saturating_add(y);
+            if pins >> y & 1 == 0 {
+                continue;
+            }
+            if pixel_y >= page_height {
+                result = result.and(Err(DecodeError::OutOfPage { offset, y: pixel_y }));
+                continue;
+            }
+            img.dot(pixel_x, pixel_y, self.color);
+            self.paper.covered.1 = self.paper.covered.1.max(pixel_y + 1);
+        }
+        result
+    }
+
+    /// Draws text dots given as offsets from the head position, clipped like
+    /// `draw_column`.
+    fn draw_dots(&mut self, offset: usize, dots: impl IntoIterator<Item = (u32, u32)>, img_mutex: &Mutex<Canvas>) -> Result<(), DecodeError> {
+        let mut img = img_mutex.lock().unwrap();
+        let page_width = img.width();
+        let page_height = img.height();
+        let mut result = Ok(());
+        for (x, y) in dots {
+            let pixel_x = self.head_x.saturating_add(x);
+            let pixel_y = self.head_y.saturating_add(y);
+            if pixel_x >= page_width {
+                continue;
+            }
+            if pixel_y >= page_height {
+                result = result.and(Err(DecodeError::OutOfPage { offset, y: pixel_y }));
+                continue;
+            }
+            img.dot(pixel_x, pixel_y, self.color);
+            self.paper.covered.0 = self.paper.covered.0.max(pixel_x + 1);
+            self.paper.covered.1 = self.paper.covered.1.max(pixel_y + 1);
+        }
+        result
+    }
+}
+
+impl Printer for Pcpr101 {
+    fn create_image(&self) -> Canvas {
+        Canvas::new(Pcpr101::PAGE_WIDTH, Pcpr101::PAGE_HEIGHT)
+    }
+
+    fn dpi(&self) -> (u32, u32) {
+        Pcpr101::DPI
+    }
+
+    fn feed_traced(&mut self, data: &[u8], img_mutex: &Mutex<Canvas>, trace: Option<&mut dyn FnMut(Trace)>) -> Result<(), DecodeError> {
+        feed_commands(self, data, img_mutex, trace)
+    }
+
+    fn finish(&mut self) -> Result<(u32, u32), DecodeError> {
+        finish_commands(self)
+    }
+
+    fn covered(&self) -> (u32, u32) {
+        self.paper.covered
+    }
+
+    fn take_pages(&mut self) -> Vec<Page> {
+        std::mem::take(&mut self.paper.pages)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(printer: &mut Pcpr101, data: &[u8]) -> (Canvas, Result<(u32, u32), DecodeError>) {
+        let img_mutex = Mutex::new(printer.create_image());
+        let result = printer.feed(data, &img_mutex).and(printer.finish());
+        (img_mutex.into_inner().unwrap(), result)
+    }
+
+    fn is_black(img: &Canvas, x: u32, y: u32) -> bool {
+        img.ink(0, x, y) == 255
+    }
+
+    const FONT: &str = "FONTBOUNDINGBOX 24 24 0 0
+ENCODING 12321
+BBX 24 1 0 23
+BITMAP
+FFFFFF
+ENDCHAR
+";
+
+    /// A single column graphics band with only the top dot set.
+    const DOT: &[u8] = b"\x1bJ0001\x01\x00\x00";
+
+    #[test]
+    fn dot_address_moves_head() {
+        let (img, result) = decode(&mut Pcpr101::default(), &[b"\x1bF0100".as_slice(), DOT].concat());
+        assert_eq!(result, Ok((101, 1)));
+        assert!(is_black(&img, 100, 0));
+    }
+
+    #[test]
+    fn left_margin_sets_return_column() {
+        let (img, result) = decode(&mut Pcpr101::default(), &[b"\x1bL010".as_slice(), DOT, b"\r", DOT, b"\n", DOT].concat());
+        assert_eq!(result, Ok((161, 25)));
+        assert!(is_black(&img, 160, 0));
+        assert!(!is_black(&img, 161, 0));
+        assert!(is_black(&img, 160, 24));
+    }
+
+    #[test]
+    fn dot_address_is_relative_to_left_margin() {
+        let (img, result) = decode(&mut Pcpr101::default(), &[b"\x1bL002\x1bF0010".as_slice(), DOT].concat());
+        assert_eq!(result, Ok((43, 1)));
+        assert!(is_black(&img, 42, 0));
+    }
+
+    #[test]
+    fn bad_dot_address_is_reported_and_ignored() {
+        let (img, result) = decode(&mut Pcpr101::default(), &[b"\x1bF01x0".as_slice(), DOT].concat());
+        assert_eq!(result, Err(DecodeError::BadNumericField { offset: 0, field: b"01x0".to_vec() }));
+        assert!(is_black(&img, 0, 0));
+        let (_, result) = decode(&mut Pcpr101::default(), b"\x1bL1 0");
+        assert_eq!(result, Err(DecodeError::BadNumericField { offset: 0, field: b"1 0".to_vec() }));
+    }
+
+    #[test]
+    fn line_feed_defaults_to_24_dots() {
+        let (img, result) = decode(&mut Pcpr101::default(), &[b"\n".as_slice(), DOT].concat());
+        assert_eq!(result, Ok((1, 25)));
+        assert!(is_black(&img, 0, 24));
+    }
+
+    #[test]
+    fn line_feed_pitch_applies_to_line_feed() {
+        let (img, result) = decode(&mut Pcpr101::default(), &[b"\x1bT20\n".as_slice(), DOT].concat());
+        assert_eq!(result, Ok((1, 31)));
+        assert!(is_black(&img, 0, 30));
+    }
+
+    #[test]
+    fn odd_line_feed_pitch_accumulates_half_dots() {
+        // 3/120 inch is 4.5 dots
+        let (img, result) = decode(&mut Pcpr101::default(), &[b"\x1bT03\n".as_slice(), DOT, b"\n", DOT].concat());
+        assert_eq!(result, Ok((1, 10)));
+        assert!(is_black(&img, 0, 4));
+        assert!(is_black(&img, 0, 9));
+    }
+
+    #[test]
+    fn bad_line_feed_pitch_is_reported_and_ignored() {
+        let (img, result) = decode(&mut Pcpr101::default(), &[b"\x1bT-1\n".as_slice(), DOT].concat());
+        assert_eq!(result, Err(DecodeError::BadNumericField { offset: 0, field: b"-1".to_vec() }));
+        assert!(is_black(&img, 0, 24));
+    }
+
+    #[test]
+    fn column_pins_for_each_mode() {
+        assert_eq!(column_pins(&[0x01, 0x80, 0x00]), 0x008001);
+        assert_eq!(column_pins(&[0x81, 0x00]), 0x000c03);
+        assert_eq!(column_pins(&[0x81]), 0xe00007);
+    }
+
+    #[test]
+    fn eight_and_sixteen_dot_graphics() {
+        let (img, result) = decode(&mut Pcpr101::default(), b"\x1bS0002\x80\x01\x1bI0001\x00\x80");
+        assert_eq!(result, Ok((3, 24)));
+        assert!(is_black(&img, 0, 21) && is_black(&img, 0, 23));
+        assert!(is_black(&img, 1, 0) && is_black(&img, 1, 2) && !is_black(&img, 1, 3));
+        assert!(is_black(&img, 2, 22) && is_black(&img, 2, 23) && !is_black(&img, 2, 21));
+    }
+
+    #[test]
+    fn repeat_graphics() {
+        let (img, result) = decode(&mut Pcpr101::default(), b"\x1bV0003\x01\x1bU0002\x00\x00\x80\x1bW0001\xff\xff");
+        assert_eq!(result, Ok((6, 24)));
+        assert!((0..3).all(|x| is_black(&img, x, 2) && !is_black(&img, x, 3)));
+        assert!((3..5).all(|x| is_black(&img, x, 23) && !is_black(&img, x, 22)));
+        assert!((0..24).all(|y| is_black(&img, 5, y)));
+    }
+
+    #[test]
+    fn kanji_mode_prints_two_byte_codes() {
+        let mut printer = Pcpr101::default();
+        printer.set_kanji_font(KanjiFont::parse_bdf(FONT).unwrap());
+        let (img, result) = decode(&mut printer, b"\x1bK0!0!\x1bHI");
+        assert_eq!(result, Ok((2 * 32 + 3 * 4, 21)));
+        assert!(is_black(&img, 0, 0));
+        assert!(is_black(&img, 23, 0));
+        assert!(!is_black(&img, 24, 0));
+        assert!(is_black(&img, 32, 0));
+        // back in ANK mode, 'I' is printed from the character ROM
+        assert!(is_black(&img, 64 + 3, 0));
+    }
+
+    #[test]
+    fn missing_kanji_print_as_boxes() {
+        let (img, result) = decode(&mut Pcpr101::default(), b"\x1bK0\"");
+        assert_eq!(result, Ok((23, 23)));
+        assert!(is_black(&img, 1, 1));
+        assert!(!is_black(&img, 2, 2));
+    }
+
+    #[test]
+    fn ank_text_without_kanji_mode() {
+        // '!' only has dots in its middle column
+        let (_, result) = decode(&mut Pcpr101::default(), b"0!");
+        assert_eq!(result, Ok((16 + 9, 21)));
+    }
+
+    #[test]
+    fn encoded_canvas_decodes_to_the_same_planes() {
+        let mut canvas = Canvas::new(40, 1010);
+        for (x, y, color) in [(0, 1, 0), (3, 23, 1), (3, 23, 2), (39, 24, 3), (10, 999, 0), (20, 1009, 2)] {
+            canvas.dot(x, y, color);
+        }
+        let data = Pcpr101::encode(&canvas);
+        assert!(data.starts_with(b"\x1bC0\x1bJ0001\x02\x00\x00\r\x1bC6\x1bJ0004"));
+        let (pages, result) = Pcpr101::default().decode(&mut &data[..]);
+        assert_eq!(result, Ok(()));
+        assert_eq!(pages.len(), 2);
+        for (page, page_y) in pages.iter().zip([0, 1000]) {
+            let expected = canvas.crop(0, page_y, 40, 1000);
+            let decoded = page.canvas.crop(0, 0, 40, expected.height());
+            assert!((0..4).all(|color| decoded.plane_data(color) == expected.plane_data(color)));
+        }
+    }
+}
@@ -0,0 +1,204 @@
+//! Properties every decoder shares, each checked on small jobs for the
+//! models it applies to. Behaviour of a single model is tested in its own
+//! module.
+
+use std::io::Read;
+use std::sync::Mutex;
+
+use super::{find_model, Cz8pc4, DecodeError, Page, Pcpr101, Printer, PrinterOptions, Trace};
+
+/// A CZ-8PC4 graphics band with only the top dot set.
+const CZ8PC4_DOT: &[u8] = &[0x1b, 0x4d, 0x00, 0x01, 0x80, 0, 0, 0, 0, 0];
+/// A PC-PR101 graphics band with only the top dot set.
+const PCPR101_DOT: &[u8] = b"\x1bJ0001\x01\x00\x00";
+
+/// The start of a CZ-6PV1 frame, `lines` lines of `val`.
+fn cz6pv1_frame(lines: usize, val: u8) -> Vec<u8> {
+    [vec![0xc0], vec![val; lines * 0x200]].concat()
+}
+
+fn new_printer(model: &str, options: &PrinterOptions) -> Box<dyn Printer> {
+    (find_model(model).unwrap().new)(options)
+}
+
+fn banner_options() -> PrinterOptions {
+    PrinterOptions { banner_length: Some(8), ..PrinterOptions::default() }
+}
+
+fn is_black(page: &Page, x: u32, y: u32) -> bool {
+    page.canvas.ink(0, x, y) == 255
+}
+
+fn same_pages(a: &[Page], b: &[Page]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(a, b)| a.covered == b.covered && (0..4).all(|color| a.canvas.plane_data(color) == b.canvas.plane_data(color)))
+}
+
+#[test]
+fn commands_split_anywhere_decode_the_same() {
+    let cases = [
+        ("cz-8pc4", [b"\n\x1bM\x00\x03".as_slice(), &[0xff, 0, 0, 0, 0, 0x01, 0x80, 0x40, 0x20, 0x10, 0x08, 0x04, 0, 0, 0, 0, 0, 0x03], b"\x1b%20\n", CZ8PC4_DOT].concat()),
+        ("pc-pr101", [b"\n\x1bJ0003".as_slice(), &[0xff, 0, 0x80, 0x01, 0x02, 0x04, 0, 0, 0xc0], b"\x1bT05\n", PCPR101_DOT].concat()),
+        // a frame cut short by the next one
+        ("cz-6pv1", [cz6pv1_frame(1, 0x80), cz6pv1_frame(1, 0xbf)].concat()),
+    ];
+    for (model, data) in cases {
+        let (expected, expected_result) = new_printer(model, &PrinterOptions::default()).decode(&mut &data[..]);
+        assert!(!expected.is_empty(), "{model}");
+        for split in 0..=data.len() {
+            let (pages, result) = new_printer(model, &PrinterOptions::default()).decode(&mut (&data[..split]).chain(&data[split..]));
+            assert_eq!(result, expected_result, "{model} split at {split}");
+            assert!(same_pages(&pages, &expected), "{model} split at {split}");
+        }
+    }
+}
+
+#[test]
+fn finish_reports_the_partial_command() {
+    let cases: [(&str, &[u8], usize); 5] = [
+        // in the header, and in the graphics data
+        ("cz-8pc4", b"\n\x1bM\x00", 1),
+        ("cz-8pc4", b"\n\x1bM\x00\x02\xff\x00\x00\x00\x00\x00\x80", 1),
+        // in the column count, and in the graphics data
+        ("pc-pr101", b"\n\x1bJ00", 1),
+        ("pc-pr101", b"\n\x1bJ0002\xff\x00\x00\x80", 1),
+        ("cz-6pv1", &[0xc0, 0x80, 0x80], 0),
+    ];
+    for (model, data, offset) in cases {
+        let mut printer = new_printer(model, &PrinterOptions::default());
+        let img_mutex = Mutex::new(printer.create_image());
+        assert_eq!(printer.feed(data, &img_mutex), Ok(()), "{model}");
+        assert_eq!(printer.finish(), Err(DecodeError::TruncatedCommand { offset }), "{model}");
+    }
+}
+
+#[test]
+fn errors_keep_what_was_decoded_before() {
+    // the dots are given as colour, x and y on the first page
+    let cases = [
+        ("cz-8pc4", [CZ8PC4_DOT, b"\x1bZ\r\n", CZ8PC4_DOT].concat(), DecodeError::UnknownCommand { offset: 10, code: b'Z' }, [(0, 0, 0), (0, 0, 48)]),
+        ("cz-8pc4", [CZ8PC4_DOT, b"\x1b%1x\r\n", CZ8PC4_DOT].concat(), DecodeError::BadNumericField { offset: 10, field: b"1x".to_vec() }, [(0, 0, 0), (0, 0, 48)]),
+        ("cz-8pc4", [CZ8PC4_DOT, b"\r\n\x1bM\x00\x02", &[0x80, 0, 0, 0, 0, 0]].concat(), DecodeError::TruncatedCommand { offset: 12 }, [(0, 0, 0), (0, 0, 48)]),
+        ("pc-pr101", [PCPR101_DOT, b"\x1bZ\r\n", PCPR101_DOT].concat(), DecodeError::UnknownCommand { offset: 9, code: b'Z' }, [(0, 0, 0), (0, 0, 24)]),
+        ("pc-pr101", [PCPR101_DOT, b"\x1bT0x\r\n", PCPR101_DOT].concat(), DecodeError::BadNumericField { offset: 9, field: b"0x".to_vec() }, [(0, 0, 0), (0, 0, 24)]),
+        ("pc-pr101", [PCPR101_DOT, b"\r\n\x1bJ0002\x01\x00\x00"].concat(), DecodeError::TruncatedCommand { offset: 11 }, [(0, 0, 0), (0, 0, 24)]),
+        ("cz-6pv1", [vec![0x55], cz6pv1_frame(2, 0x80)].concat(), DecodeError::UnknownCommand { offset: 0, code: 0x55 }, [(1, 0, 0), (1, 0, 1)]),
+        ("cz-6pv1", cz6pv1_frame(2, 0x80), DecodeError::TruncatedCommand { offset: 0 }, [(1, 0, 0), (1, 0, 1)]),
+    ];
+    for (model, data, error, dots) in cases {
+        let (pages, result) = new_printer(model, &PrinterOptions::default()).decode(&mut &data[..]);
+        assert_eq!(result, Err(error), "{model}");
+        assert!(dots.iter().all(|&(color, x, y)| pages[0].canvas.ink(color, x, y) == 255), "{model} {data:x?}");
+    }
+}
+
+#[test]
+fn traces_describe_each_command() {
+    let cases = [
+        ("cz-8pc4", [b"\x1b\x19".as_slice(), CZ8PC4_DOT, b"\r\n\x1bZ"].concat(), vec![
+            (0, b"\x1b\x19".as_slice(), "ESC 0x19 colour mode, colour plane yellow"),
+            (2, b"\x1bM\x00\x01", "ESC M 48-dot graphics, 1 columns"),
+            (12, b"\r", "CR \u{2192} colour plane magenta"),
+            (13, b"\n", "LF +48 dots, row 48"),
+            (14, b"\x1bZ", "unsupported ESC 0x5a"),
+        ]),
+        ("pc-pr101", [PCPR101_DOT, b"\n\x1bC6\r\x1bZ"].concat(), vec![
+            (0, b"\x1bJ0001".as_slice(), "ESC J 24-dot graphics, 1 columns"),
+            (9, b"\n", "LF +16/120 inch, row 24"),
+            (10, b"\x1bC6", "ESC C select colour yellow"),
+            (13, b"\r", "CR"),
+            (14, b"\x1bZ", "unsupported ESC 0x5a"),
+        ]),
+    ];
+    for (model, data, expected) in cases {
+        let mut printer = new_printer(model, &PrinterOptions::default());
+        let img_mutex = Mutex::new(printer.create_image());
+        let mut traces = Vec::new();
+        let result = printer.feed_traced(&data, &img_mutex, Some(&mut |trace: Trace| traces.push((trace.offset, trace.raw.to_vec(), trace.description))));
+        assert_eq!(result, Err(DecodeError::UnknownCommand { offset: 14, code: b'Z' }), "{model}");
+        let expected: Vec<_> = expected.into_iter().map(|(offset, raw, description)| (offset, raw.to_vec(), description.to_string())).collect();
+        assert_eq!(traces, expected, "{model}");
+    }
+}
+
+#[test]
+fn head_stops_at_the_last_position() {
+    let mut cz8pc4_banner = Cz8pc4::with_head(0, u32::MAX - 10);
+    cz8pc4_banner.set_banner(3000);
+    let mut pcpr101_banner = Pcpr101::with_head(0, u32::MAX - 10);
+    pcpr101_banner.set_banner(1500);
+    let cases = [
+        ("cz-8pc4", Box::new(Cz8pc4::with_head(u32::MAX - 40, 0)) as Box<dyn Printer>, [b"II".as_slice(), CZ8PC4_DOT].concat(), Ok((Cz8pc4::PAGE_WIDTH, 0))),
+        ("cz-8pc4", Box::new(cz8pc4_banner), [b"\n\x0c".as_slice(), CZ8PC4_DOT].concat(), Err(DecodeError::OutOfPage { offset: 2, y: u32::MAX })),
+        // about 4 MB of repeats get the head here
+        ("pc-pr101", Box::new(Pcpr101::with_head(u32::MAX - 9000, 0)), b"\x1bU9999\xff\xff\xffI\x1bK0!".to_vec(), Ok((Pcpr101::PAGE_WIDTH, 0))),
+        ("pc-pr101", Box::new(pcpr101_banner), [b"\n\x0c".as_slice(), PCPR101_DOT].concat(), Err(DecodeError::OutOfPage { offset: 2, y: u32::MAX })),
+    ];
+    for (model, mut printer, data, expected) in cases {
+        let img_mutex = Mutex::new(printer.create_image());
+        assert_eq!(printer.feed(&data, &img_mutex).and(printer.finish()), expected, "{model}");
+    }
+}
+
+#[test]
+fn form_feed_starts_a_new_page() {
+    let cases = [
+        ("cz-8pc4", [CZ8PC4_DOT, b"\n\x0c", CZ8PC4_DOT].concat(), (1, 48), (0, 0)),
+        // pages with only the top row printed are kept
+        ("pc-pr101", [b"\x1bL002".as_slice(), PCPR101_DOT, b"\n\n\x0c", PCPR101_DOT].concat(), (33, 1), (32, 0)),
+    ];
+    for (model, data, covered, (x, y)) in cases {
+        let (pages, result) = new_printer(model, &PrinterOptions::default()).decode(&mut &data[..]);
+        assert_eq!(result, Ok(()), "{model}");
+        assert_eq!(pages.len(), 2, "{model}");
+        assert!(pages.iter().all(|page| page.covered == covered && is_black(page, x, y)), "{model}");
+    }
+}
+
+#[test]
+fn line_feed_past_the_bottom_continues_on_the_next_page() {
+    let cases = [
+        ("cz-8pc4", [CZ8PC4_DOT, b"\x1b%99\n\n\n\n\n\n\n", CZ8PC4_DOT].concat(), 7 * 297 - 2000, (1, 79 + 48)),
+        // 42 lines of 24 dots and one of 12
+        ("pc-pr101", [b"\x1bJ0001\x00\x00\x80".as_slice(), b"\x1bT16", &[b'\n'; 42], b"\x1bT08\n", PCPR101_DOT].concat(), 42 * 24 + 12 - 1000, (1, 21)),
+    ];
+    for (model, data, y, covered) in cases {
+        let (pages, result) = new_printer(model, &PrinterOptions::default()).decode(&mut &data[..]);
+        assert_eq!(result, Ok(()), "{model}");
+        assert_eq!(pages.len(), 2, "{model}");
+        assert!(is_black(&pages[1], 0, y), "{model}");
+        assert_eq!(pages[1].covered, covered, "{model}");
+    }
+}
+
+#[test]
+fn banner_grows_instead_of_starting_a_new_page() {
+    // banners of 8 inches, the dots are given as x and y
+    let cases = [
+        // the form feed moves on to the start of the second page, the last
+        // band is past the end of the banner
+        ("cz-8pc4", [CZ8PC4_DOT, b"\x1b%99\n\n\n\n\n\n\x0c", CZ8PC4_DOT, b"\n\n\n\n", CZ8PC4_DOT].concat(), 8 * 360, vec![(0, 0), (0, 2000)], (1, 2000 + 48),
+            Err(DecodeError::OutOfPage { offset: 35, y: 2000 + 4 * 297 })),
+        ("pc-pr101", [b"\x1bT99".as_slice(), &[b'\n'; 7], PCPR101_DOT].concat(), 8 * 180, vec![(0, 7 * 99 * 3 / 2)], (1, 7 * 99 * 3 / 2 + 1), Ok(())),
+    ];
+    for (model, data, height, dots, covered, expected) in cases {
+        let (pages, result) = new_printer(model, &banner_options()).decode(&mut &data[..]);
+        assert_eq!(result, expected, "{model}");
+        assert_eq!(pages.len(), 1, "{model}");
+        assert_eq!(pages[0].canvas.height(), height, "{model}");
+        assert!(dots.iter().all(|&(x, y)| is_black(&pages[0], x, y)), "{model}");
+        assert_eq!(pages[0].covered, covered, "{model}");
+    }
+}
+
+#[test]
+fn banner_form_feed_at_the_top_of_a_page_moves_on() {
+    let cases = [
+        ("cz-8pc4", [b"\x0c".as_slice(), CZ8PC4_DOT].concat(), 2000),
+        ("pc-pr101", [b"\x0c".as_slice(), PCPR101_DOT].concat(), 1000),
+    ];
+    for (model, data, y) in cases {
+        let (pages, result) = new_printer(model, &banner_options()).decode(&mut &data[..]);
+        assert_eq!(result, Ok(()), "{model}");
+        assert!(!is_black(&pages[0], 0, 0) && is_black(&pages[0], 0, y), "{model}");
+    }
+}